@@ -0,0 +1,102 @@
+//! Bearer-token authentication for network transports. Each token grants a
+//! scope; the dispatcher checks it against the scope a tool requires.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use sha2::{Digest, Sha256};
+
+/// Environment variable holding tokens, as an alternative to a tokens file.
+pub const TOKENS_ENV: &str = "SQLITE_MCP_TOKENS";
+
+/// What a client may do. Each scope includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    /// Read tables, schemas and comments
+    Read,
+    /// Also insert, update and delete rows and edit comments
+    Write,
+    /// Also change the schema: DDL, alter_table and migrations
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(anyhow!("Unknown scope '{}'; expected read, write or admin", s)),
+        }
+    }
+}
+
+/// Tokens accepted by the server. Only SHA-256 digests are kept, so lookups
+/// do not compare secrets byte by byte.
+#[derive(Debug, Clone, Default)]
+pub struct TokenStore {
+    tokens: HashMap<[u8; 32], Scope>,
+}
+
+impl TokenStore {
+    /// Parse `scope:token` entries separated by newlines, commas or spaces.
+    /// Lines starting with `#` are comments.
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut tokens = HashMap::new();
+        for entry in source
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split([',', ' ', '\t']))
+            .filter(|entry| !entry.is_empty())
+        {
+            let (scope, token) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid token entry, expected scope:token"))?;
+            if token.is_empty() {
+                return Err(anyhow!("Empty token for scope '{}'", scope));
+            }
+            tokens.insert(digest(token), scope.parse()?);
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Tokens from `path` if given, otherwise from `SQLITE_MCP_TOKENS`.
+    /// Returns `None` when neither is set.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        let source = match path {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read tokens file {:?}", path))?,
+            None => match std::env::var(TOKENS_ENV) {
+                Ok(source) => source,
+                Err(_) => return Ok(None),
+            },
+        };
+
+        let store = Self::parse(&source)?;
+        if store.tokens.is_empty() {
+            return Err(anyhow!("No tokens configured"));
+        }
+        Ok(Some(store))
+    }
+
+    pub fn scope(&self, token: &str) -> Option<Scope> {
+        self.tokens.get(&digest(token)).copied()
+    }
+}
+
+pub fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
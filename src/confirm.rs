@@ -0,0 +1,133 @@
+//! Confirmation of destructive tool calls. Before deleting rows, or updating
+//! more rows than the configured threshold, the server describes the change
+//! and asks the user through MCP elicitation. Large changes come with a
+//! preview of the affected rows, selected with the same key filter the tool
+//! will use.
+
+use std::sync::Arc;
+
+use crate::db::adapter::QueryFilter;
+use crate::db::DatabaseAdapter;
+use crate::tools::{batch, delete, parse_arguments, update};
+
+/// Rows shown in the preview of a large change.
+const SAMPLE_ROWS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Delete,
+    Update,
+}
+
+/// A change waiting for the user's approval.
+#[derive(Debug)]
+pub struct Change {
+    action: Action,
+    table: String,
+    count: usize,
+    /// Some of the affected rows; empty below the threshold
+    sample: Vec<serde_json::Value>,
+}
+
+impl Change {
+    pub fn message(&self) -> String {
+        let verb = match self.action {
+            Action::Delete => "Delete",
+            Action::Update => "Update",
+        };
+        let rows = if self.count == 1 { "row" } else { "rows" };
+        let mut message = format!("{} {} {} in table '{}'?", verb, self.count, rows, self.table);
+        if !self.sample.is_empty() {
+            message.push_str(&format!("\n\nShowing {} of {}:", self.sample.len(), self.count));
+            for row in &self.sample {
+                message.push_str(&format!("\n{}", row));
+            }
+        }
+        message
+    }
+}
+
+/// The change `tool` would make with `arguments`, if the user has to confirm
+/// it first. Deletes always need confirmation, updates only above
+/// `threshold`. Arguments the tool would reject need none; the tool itself
+/// reports them.
+pub async fn pending_change(
+    db: &Arc<dyn DatabaseAdapter>,
+    tool: &str,
+    arguments: &serde_json::Value,
+    threshold: usize,
+) -> anyhow::Result<Option<Change>> {
+    let target = match tool {
+        "delete_record" => parse_arguments::<delete::DeleteRecordInput>(arguments)
+            .map(|input| (Action::Delete, input.table, vec![input.id])),
+        "batch_delete" => parse_arguments::<batch::BatchDeleteInput>(arguments)
+            .map(|input| (Action::Delete, input.table, input.ids)),
+        "update_record" => parse_arguments::<update::UpdateRecordInput>(arguments)
+            .map(|input| (Action::Update, input.table, vec![input.id])),
+        "batch_update" => parse_arguments::<batch::BatchUpdateInput>(arguments).map(|input| {
+            let ids = input.updates.iter().map(|item| item.id).collect();
+            (Action::Update, input.table, ids)
+        }),
+        _ => return Ok(None),
+    };
+    let Ok((action, table, ids)) = target else {
+        return Ok(None);
+    };
+    // Nothing to confirm when the write is refused anyway
+    if ids.is_empty() || db.is_readonly().await {
+        return Ok(None);
+    }
+
+    // Same key the tool matches on: the single primary key, otherwise rowid
+    let key = db
+        .get_schema(&table)
+        .await?
+        .primary_key
+        .unwrap_or_else(|| "rowid".to_string());
+    let filter: QueryFilter = serde_json::from_value(serde_json::json!({ key: { "$in": ids } }))?;
+
+    let count = db.count(&table, Some(filter.clone())).await?;
+    let large = count > threshold;
+    if count == 0 || (action == Action::Update && !large) {
+        return Ok(None);
+    }
+    let sample = if large {
+        db.select(&table, Some(filter), None, Some(SAMPLE_ROWS), None)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Some(Change {
+        action,
+        table,
+        count,
+        sample,
+    }))
+}
+
+/// Form shown to the user: a single checkbox.
+pub fn requested_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "confirm": {
+                "type": "boolean",
+                "title": "Confirm",
+                "description": "Apply this change"
+            }
+        },
+        "required": ["confirm"]
+    })
+}
+
+/// Only an accepted form with the box ticked counts as consent; declining,
+/// cancelling or accepting without ticking it does not.
+pub fn accepted(response: &serde_json::Value) -> bool {
+    response.get("action").and_then(|a| a.as_str()) == Some("accept")
+        && response
+            .get("content")
+            .and_then(|content| content.get("confirm"))
+            .and_then(|confirm| confirm.as_bool())
+            == Some(true)
+}
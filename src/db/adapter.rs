@@ -2,10 +2,14 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+use crate::db::migrations::{MigrationCommand, MigrationReport};
+use crate::db::schema_diff::SchemaDiff;
+
 #[async_trait]
 pub trait DatabaseAdapter: Send + Sync {
-    async fn list_tables(&self) -> Result<Vec<String>>;
+    async fn list_tables(&self, include_internal: bool) -> Result<Vec<String>>;
     async fn get_schema(&self, table: &str) -> Result<TableSchema>;
+    async fn list_schemas(&self) -> Result<Vec<TableSchema>>;
     async fn select(
         &self,
         table: &str,
@@ -38,20 +42,66 @@ pub trait DatabaseAdapter: Send + Sync {
     async fn batch_delete(&self, table: &str, ids: Vec<i64>) -> Result<usize>;
     async fn set_table_comment(&self, table: &str, desc: &str) -> Result<()>;
     async fn set_column_comment(&self, table: &str, column: &str, desc: &str) -> Result<()>;
+    async fn set_column_metadata(
+        &self,
+        table: &str,
+        column: &str,
+        metadata: ColumnMetadata,
+    ) -> Result<()>;
+    async fn create_table(&self, definition: TableDefinition) -> Result<TableSchema>;
+    async fn add_column(&self, table: &str, column: ColumnDefinition) -> Result<TableSchema>;
+    async fn create_index(&self, index: IndexDefinition) -> Result<TableSchema>;
+    async fn drop_index(&self, name: &str, if_exists: bool) -> Result<Option<TableSchema>>;
+    async fn alter_table(
+        &self,
+        table: &str,
+        operation: AlterTableOperation,
+    ) -> Result<AlterTableResult>;
+    async fn migrate(
+        &self,
+        dir: &std::path::Path,
+        command: MigrationCommand,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<MigrationReport>;
+    async fn schema_diff(
+        &self,
+        source: Option<&std::path::Path>,
+        target: &std::path::Path,
+        include_sql: bool,
+    ) -> Result<SchemaDiff>;
+    async fn import_comments(&self, document: CommentDocument) -> Result<ImportCommentsResult>;
 
     #[allow(dead_code)]
     async fn is_readonly(&self) -> bool;
+    /// Short name of the database, used in resource URIs
+    async fn database_name(&self) -> String;
+    async fn subscribe_changes(&self) -> tokio::sync::broadcast::Receiver<DataChange>;
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// A committed change to the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataChange {
+    /// Rows of these tables were written through this server's connection
+    Tables(Vec<String>),
+    /// Another process committed to the database; any table may have changed
+    External,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
 pub struct TableSchema {
     pub name: String,
     pub desc: Option<String>,
     pub columns: Vec<ColumnInfo>,
     pub primary_key: Option<String>,
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub triggers: Vec<TriggerInfo>,
+    /// Expressions of the table's CHECK constraints
+    pub checks: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
 pub struct ColumnInfo {
     pub name: String,
     pub desc: Option<String>,
@@ -59,6 +109,83 @@ pub struct ColumnInfo {
     pub not_null: bool,
     pub default_value: Option<String>,
     pub is_primary_key: bool,
+    #[serde(flatten)]
+    pub metadata: ColumnMetadata,
+}
+
+/// Structured column metadata stored alongside the column comment.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ColumnMetadata {
+    /// Unit of measure, e.g. `ms`, `USD`, `kg`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// The only values writes may store in the column (NULL is always allowed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<serde_json::Value>>,
+    /// Representative values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub examples: Option<Vec<serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ColumnFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<Sensitivity>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+}
+
+/// How the values of a column are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnFormat {
+    /// `YYYY-MM-DD` text
+    Date,
+    /// ISO 8601 date and time text
+    Datetime,
+    /// Seconds since the Unix epoch
+    Epoch,
+    /// JSON text
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Sensitivity {
+    Public,
+    Internal,
+    /// Personally identifiable information, masked in query results
+    Pii,
+    /// Credentials and other secrets, masked in query results
+    Secret,
+}
+
+impl Sensitivity {
+    pub fn is_masked(&self) -> bool {
+        matches!(self, Sensitivity::Pii | Sensitivity::Secret)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub struct ForeignKeyInfo {
+    pub columns: Vec<String>,
+    pub ref_table: String,
+    pub ref_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// How the index was created: `c` (CREATE INDEX), `u` (UNIQUE constraint) or `pk`
+    pub origin: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+pub struct TriggerInfo {
+    pub name: String,
+    pub sql: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -70,7 +197,8 @@ pub struct BatchResult {
     pub inserted_ids: Option<Vec<i64>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+/// Filter conditions keyed by column name, combined with AND.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryFilter {
     #[serde(flatten)]
     pub conditions: std::collections::HashMap<String, FilterValue>,
@@ -80,7 +208,7 @@ pub struct QueryFilter {
 #[serde(untagged)]
 pub enum FilterValue {
     Direct(serde_json::Value),
-    Operator(FilterOperators),
+    Operator(Box<FilterOperators>),
 }
 
 impl<'de> serde::Deserialize<'de> for FilterValue {
@@ -95,7 +223,7 @@ impl<'de> serde::Deserialize<'de> for FilterValue {
             if obj.keys().any(|k| k.starts_with('$')) {
                 // 尝试解析为 FilterOperators
                 if let Ok(ops) = serde_json::from_value::<FilterOperators>(value.clone()) {
-                    return Ok(FilterValue::Operator(ops));
+                    return Ok(FilterValue::Operator(Box::new(ops)));
                 }
             }
         }
@@ -104,29 +232,37 @@ impl<'de> serde::Deserialize<'de> for FilterValue {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, schemars::JsonSchema)]
 pub struct FilterOperators {
+    /// Equals
     #[serde(rename = "$eq")]
     #[serde(default)]
     pub eq: Option<serde_json::Value>,
+    /// Not equals
     #[serde(rename = "$ne")]
     #[serde(default)]
     pub ne: Option<serde_json::Value>,
+    /// Greater than
     #[serde(rename = "$gt")]
     #[serde(default)]
     pub gt: Option<serde_json::Value>,
+    /// Greater than or equal
     #[serde(rename = "$gte")]
     #[serde(default)]
     pub gte: Option<serde_json::Value>,
+    /// Less than
     #[serde(rename = "$lt")]
     #[serde(default)]
     pub lt: Option<serde_json::Value>,
+    /// Less than or equal
     #[serde(rename = "$lte")]
     #[serde(default)]
     pub lte: Option<serde_json::Value>,
+    /// Value in list
     #[serde(rename = "$in")]
     #[serde(default)]
     pub in_list: Option<Vec<serde_json::Value>>,
+    /// Pattern matching (use % for wildcard)
     #[serde(rename = "$like")]
     #[serde(default)]
     pub like: Option<String>,
@@ -141,20 +277,226 @@ pub enum OrderDirection {
 
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct OrderClause {
+    /// Column name for ordering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub column: Option<String>,
+    /// Sort direction (default: desc)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub direction: Option<OrderDirection>,
+    /// Use random ordering (mutually exclusive with column/direction)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub random: Option<bool>,
 }
 
+impl schemars::JsonSchema for QueryFilter {
+    fn schema_name() -> String {
+        "QueryFilter".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        gen.subschema_for::<std::collections::HashMap<String, FilterValue>>()
+    }
+}
+
 impl schemars::JsonSchema for FilterValue {
     fn schema_name() -> String {
         "FilterValue".to_string()
     }
 
     fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
-        gen.subschema_for::<serde_json::Value>()
+        // Either an operator object or a value matched with `=`
+        schemars::schema::SchemaObject {
+            subschemas: Some(Box::new(schemars::schema::SubschemaValidation {
+                any_of: Some(vec![
+                    gen.subschema_for::<FilterOperators>(),
+                    gen.subschema_for::<serde_json::Value>(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Structured table definition used by the DDL tools.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TableDefinition {
+    /// Table name
+    pub name: String,
+    /// Table description, stored in `_table_comment`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    /// Column definitions, in order
+    pub columns: Vec<ColumnDefinition>,
+    /// Table-level foreign key constraints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub foreign_keys: Vec<ForeignKeyDefinition>,
+    /// Table-level UNIQUE constraints, each a list of column names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unique: Vec<Vec<String>>,
+    /// Table-level CHECK constraint expressions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<String>,
+    /// Do nothing if the table already exists
+    #[serde(default)]
+    pub if_not_exists: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ColumnDefinition {
+    /// Column name
+    pub name: String,
+    /// Declared SQLite type, e.g. INTEGER, TEXT, REAL, BLOB, NUMERIC(10,2)
+    #[serde(default)]
+    pub data_type: String,
+    /// Column description, stored in `_table_column_comment`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    #[serde(default)]
+    pub not_null: bool,
+    /// Single-column primary key (composite primary keys are not supported)
+    #[serde(default)]
+    pub primary_key: bool,
+    /// Only valid on an INTEGER primary key
+    #[serde(default)]
+    pub autoincrement: bool,
+    #[serde(default)]
+    pub unique: bool,
+    /// Literal default value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Default as a raw SQL expression, e.g. CURRENT_TIMESTAMP (mutually exclusive with `default`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_expr: Option<String>,
+    /// Column CHECK constraint expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ForeignKeyDefinition {
+    /// Referencing columns in this table
+    pub columns: Vec<String>,
+    /// Referenced table
+    pub ref_table: String,
+    /// Referenced columns (defaults to the referenced table's primary key)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ref_columns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_delete: Option<ForeignKeyAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_update: Option<ForeignKeyAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ForeignKeyAction {
+    NoAction,
+    Restrict,
+    SetNull,
+    SetDefault,
+    Cascade,
+}
+
+impl ForeignKeyAction {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ForeignKeyAction::NoAction => "NO ACTION",
+            ForeignKeyAction::Restrict => "RESTRICT",
+            ForeignKeyAction::SetNull => "SET NULL",
+            ForeignKeyAction::SetDefault => "SET DEFAULT",
+            ForeignKeyAction::Cascade => "CASCADE",
+        }
+    }
+
+    /// Parse an action as reported by `PRAGMA foreign_key_list`. `NO ACTION`
+    /// is the default and maps to `None`.
+    pub fn from_sql(action: &str) -> Option<Self> {
+        match action.to_uppercase().as_str() {
+            "RESTRICT" => Some(ForeignKeyAction::Restrict),
+            "SET NULL" => Some(ForeignKeyAction::SetNull),
+            "SET DEFAULT" => Some(ForeignKeyAction::SetDefault),
+            "CASCADE" => Some(ForeignKeyAction::Cascade),
+            _ => None,
+        }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct IndexDefinition {
+    /// Index name
+    pub name: String,
+    /// Table to index
+    pub table: String,
+    /// Indexed columns, in order
+    pub columns: Vec<String>,
+    /// Create a UNIQUE index
+    #[serde(default)]
+    pub unique: bool,
+    /// Do nothing if the index already exists
+    #[serde(default)]
+    pub if_not_exists: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlterTableOperation {
+    /// Rename the table
+    RenameTable { new_name: String },
+    /// Rename a column; its comment follows the rename
+    RenameColumn { column: String, new_name: String },
+    /// Drop a column along with the indexes and constraints that use it
+    DropColumn { column: String },
+    /// Change the declared type of a column
+    ChangeType { column: String, data_type: String },
+    /// Add a table constraint
+    AddConstraint { constraint: ConstraintDefinition },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConstraintDefinition {
+    Unique { columns: Vec<String> },
+    Check { expr: String },
+    NotNull { column: String },
+    ForeignKey(ForeignKeyDefinition),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlterStrategy {
+    /// Applied with a native `ALTER TABLE` statement
+    Native,
+    /// Applied by copying the data into a rebuilt table
+    Rebuild,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct AlterTableResult {
+    pub strategy: AlterStrategy,
+    /// Indexes and constraints that were removed along with a dropped column
+    pub removed: Vec<String>,
+    pub schema: TableSchema,
+}
+
+/// Comments for many tables at once: `{table: {desc, columns: {column: desc}}}`.
+pub type CommentDocument = std::collections::BTreeMap<String, TableComments>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TableComments {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub columns: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+pub struct ImportCommentsResult {
+    pub tables_updated: usize,
+    pub columns_updated: usize,
+    /// Tables in the document that do not exist; their comments were skipped
+    pub unknown_tables: Vec<String>,
+    /// `table.column` entries that do not exist; their comments were skipped
+    pub unknown_columns: Vec<String>,
+}
@@ -0,0 +1,68 @@
+//! Cancellation of in-flight requests. The server runs each request inside
+//! `CURRENT` with its own token; database work picks the token up and lets
+//! `cancel` interrupt the SQLite statement running on its behalf.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::db::error::DbError;
+
+tokio::task_local! {
+    /// Token of the request the current task is handling.
+    pub static CURRENT: CancelToken;
+}
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    /// Interrupt handle of the connection while it runs work for this request
+    running: Mutex<Option<Arc<rusqlite::InterruptHandle>>>,
+}
+
+impl CancelToken {
+    /// The token of the request handled by the current task, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|token| token.clone()).ok()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(running) = self.0.running.lock() {
+            if let Some(handle) = running.as_ref() {
+                handle.interrupt();
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Run `f` on a connection with `handle` registered, so that `cancel`
+    /// interrupts it. Fails without running `f` if the request was already
+    /// cancelled.
+    pub fn run_interruptible<T>(
+        &self,
+        handle: &Arc<rusqlite::InterruptHandle>,
+        f: impl FnOnce() -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        self.set_running(Some(handle.clone()));
+        let result = if self.is_cancelled() {
+            Err(DbError::Cancelled.into())
+        } else {
+            f()
+        };
+        // Unregister before the connection is handed to the next request
+        self.set_running(None);
+        result
+    }
+
+    fn set_running(&self, handle: Option<Arc<rusqlite::InterruptHandle>>) {
+        if let Ok(mut running) = self.0.running.lock() {
+            *running = handle;
+        }
+    }
+}
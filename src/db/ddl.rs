@@ -0,0 +1,488 @@
+use std::collections::HashSet;
+
+use crate::db::adapter::{ColumnDefinition, ForeignKeyDefinition, IndexDefinition, TableDefinition};
+use crate::db::error::DbError;
+
+/// Quote an identifier for use in generated SQL.
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote a string literal for use in generated SQL.
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn quote_ident_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| quote_ident(n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn validate_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    if name.trim().is_empty() {
+        return Err(DbError::InvalidArgument(format!("{} name must not be empty", kind)).into());
+    }
+    if name.to_lowercase().starts_with("sqlite_") {
+        return Err(DbError::InvalidArgument(format!(
+            "{} name '{}' is reserved for internal use by SQLite",
+            kind,
+            name
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Declared types are interpolated into DDL as-is, so only allow the
+/// characters that can appear in a SQLite type name.
+fn validate_data_type(data_type: &str) -> anyhow::Result<()> {
+    let valid = data_type
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '(' | ')' | ',' | '.' | '+' | '-'));
+    if !valid {
+        return Err(DbError::InvalidArgument(format!("Invalid column type '{}'", data_type)).into());
+    }
+    Ok(())
+}
+
+fn default_literal(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => quote_literal(s),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            quote_literal(&value.to_string())
+        }
+    }
+}
+
+pub fn validate_column_definition(column: &ColumnDefinition) -> anyhow::Result<()> {
+    validate_name("Column", &column.name)?;
+    validate_data_type(&column.data_type)?;
+
+    if column.default.is_some() && column.default_expr.is_some() {
+        return Err(DbError::InvalidArgument(format!(
+            "Column '{}': 'default' and 'default_expr' are mutually exclusive",
+            column.name
+        ))
+        .into());
+    }
+
+    if column.autoincrement
+        && !(column.primary_key && column.data_type.eq_ignore_ascii_case("INTEGER"))
+    {
+        return Err(DbError::InvalidArgument(format!(
+            "Column '{}': AUTOINCREMENT is only allowed on an INTEGER primary key",
+            column.name
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn validate_table_definition(definition: &TableDefinition) -> anyhow::Result<()> {
+    validate_name("Table", &definition.name)?;
+
+    if definition.columns.is_empty() {
+        return Err(DbError::InvalidArgument(format!(
+            "Table '{}' must have at least one column",
+            definition.name
+        ))
+        .into());
+    }
+
+    let mut names = HashSet::new();
+    for column in &definition.columns {
+        validate_column_definition(column)?;
+        if !names.insert(column.name.to_lowercase()) {
+            return Err(DbError::InvalidArgument(format!(
+                "Duplicate column '{}'", column.name
+            ))
+            .into());
+        }
+    }
+
+    let pk_count = definition.columns.iter().filter(|c| c.primary_key).count();
+    if pk_count > 1 {
+        return Err(DbError::Unsupported(
+            "Composite primary keys not supported".to_string(),
+        )
+        .into());
+    }
+
+    let check_columns = |columns: &[String], what: &str| -> anyhow::Result<()> {
+        if columns.is_empty() {
+            return Err(DbError::InvalidArgument(format!(
+                "{} must list at least one column", what
+            ))
+            .into());
+        }
+        for column in columns {
+            if !names.contains(&column.to_lowercase()) {
+                return Err(DbError::InvalidArgument(format!(
+                    "{} references unknown column '{}'",
+                    what,
+                    column
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    };
+
+    for unique in &definition.unique {
+        check_columns(unique, "UNIQUE constraint")?;
+    }
+
+    for fk in &definition.foreign_keys {
+        check_columns(&fk.columns, "Foreign key")?;
+        if !fk.ref_columns.is_empty() && fk.ref_columns.len() != fk.columns.len() {
+            return Err(DbError::InvalidArgument(format!(
+                "Foreign key on ({}) has {} columns but references {}",
+                fk.columns.join(", "),
+                fk.columns.len(),
+                fk.ref_columns.len()
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn column_sql(column: &ColumnDefinition) -> String {
+    let mut sql = quote_ident(&column.name);
+
+    if !column.data_type.trim().is_empty() {
+        sql.push(' ');
+        sql.push_str(column.data_type.trim());
+    }
+    if column.primary_key {
+        sql.push_str(" PRIMARY KEY");
+        if column.autoincrement {
+            sql.push_str(" AUTOINCREMENT");
+        }
+    }
+    if column.not_null {
+        sql.push_str(" NOT NULL");
+    }
+    if column.unique {
+        sql.push_str(" UNIQUE");
+    }
+    if let Some(default) = &column.default {
+        sql.push_str(&format!(" DEFAULT {}", default_literal(default)));
+    } else if let Some(expr) = &column.default_expr {
+        sql.push_str(&format!(" DEFAULT ({})", expr));
+    }
+    if let Some(check) = &column.check {
+        sql.push_str(&format!(" CHECK ({})", check));
+    }
+
+    sql
+}
+
+fn foreign_key_sql(fk: &ForeignKeyDefinition) -> String {
+    let mut sql = format!(
+        "FOREIGN KEY ({}) REFERENCES {}",
+        quote_ident_list(&fk.columns),
+        quote_ident(&fk.ref_table)
+    );
+    if !fk.ref_columns.is_empty() {
+        sql.push_str(&format!(" ({})", quote_ident_list(&fk.ref_columns)));
+    }
+    if let Some(action) = fk.on_delete {
+        sql.push_str(&format!(" ON DELETE {}", action.as_sql()));
+    }
+    if let Some(action) = fk.on_update {
+        sql.push_str(&format!(" ON UPDATE {}", action.as_sql()));
+    }
+    sql
+}
+
+/// Render a `CREATE TABLE` statement for `definition` under `table_name`.
+///
+/// The name is passed separately so a definition can be materialised under a
+/// temporary name during a table rebuild.
+pub fn create_table_sql(definition: &TableDefinition, table_name: &str) -> String {
+    let mut parts: Vec<String> = definition.columns.iter().map(column_sql).collect();
+
+    for unique in &definition.unique {
+        parts.push(format!("UNIQUE ({})", quote_ident_list(unique)));
+    }
+    for check in &definition.checks {
+        parts.push(format!("CHECK ({})", check));
+    }
+    for fk in &definition.foreign_keys {
+        parts.push(foreign_key_sql(fk));
+    }
+
+    format!(
+        "CREATE TABLE {}{} (\n  {}\n)",
+        if definition.if_not_exists {
+            "IF NOT EXISTS "
+        } else {
+            ""
+        },
+        quote_ident(table_name),
+        parts.join(",\n  ")
+    )
+}
+
+pub fn add_column_sql(table: &str, column: &ColumnDefinition) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN {}",
+        quote_ident(table),
+        column_sql(column)
+    )
+}
+
+pub fn create_index_sql(index: &IndexDefinition) -> String {
+    format!(
+        "CREATE {}INDEX {}{} ON {} ({})",
+        if index.unique { "UNIQUE " } else { "" },
+        if index.if_not_exists {
+            "IF NOT EXISTS "
+        } else {
+            ""
+        },
+        quote_ident(&index.name),
+        quote_ident(&index.table),
+        quote_ident_list(&index.columns)
+    )
+}
+
+pub fn validate_index_definition(index: &IndexDefinition) -> anyhow::Result<()> {
+    validate_name("Index", &index.name)?;
+    if index.columns.is_empty() {
+        return Err(DbError::InvalidArgument(format!(
+            "Index '{}' must list at least one column", index.name
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Literal,
+    Punct(char),
+}
+
+/// Split SQL into tokens with their byte ranges. Only as much of the SQLite
+/// grammar as is needed to find identifiers and balanced parentheses.
+fn tokenize(sql: &str) -> Vec<(Token, usize, usize)> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let read_quoted = |start: usize, close: u8, doubled: bool| -> (String, usize) {
+        let mut content = Vec::new();
+        let mut j = start + 1;
+        while j < bytes.len() {
+            if bytes[j] == close {
+                if doubled && j + 1 < bytes.len() && bytes[j + 1] == close {
+                    content.push(close);
+                    j += 2;
+                    continue;
+                }
+                return (String::from_utf8_lossy(&content).into_owned(), j + 1);
+            }
+            content.push(bytes[j]);
+            j += 1;
+        }
+        (String::from_utf8_lossy(&content).into_owned(), bytes.len())
+    };
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == b'-' && bytes.get(i + 1) == Some(&b'-') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if c == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            i += 2;
+            while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                i += 1;
+            }
+            i = (i + 2).min(bytes.len());
+        } else if c == b'\'' {
+            let (_, end) = read_quoted(i, b'\'', true);
+            tokens.push((Token::Literal, i, end));
+            i = end;
+        } else if c == b'"' || c == b'`' {
+            let (name, end) = read_quoted(i, c, true);
+            tokens.push((Token::Quoted(name), i, end));
+            i = end;
+        } else if c == b'[' {
+            let (name, end) = read_quoted(i, b']', false);
+            tokens.push((Token::Quoted(name), i, end));
+            i = end;
+        } else if c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80 {
+            let start = i;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'_'
+                    || bytes[i] == b'$'
+                    || bytes[i] >= 0x80)
+            {
+                i += 1;
+            }
+            tokens.push((Token::Word(sql[start..i].to_string()), start, i));
+        } else {
+            tokens.push((Token::Punct(c as char), i, i + 1));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Extract the expressions of every `CHECK (...)` constraint, column or
+/// table level, from a `CREATE TABLE` statement.
+pub fn extract_check_constraints(create_sql: &str) -> Vec<String> {
+    let tokens = tokenize(create_sql);
+    let mut checks = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_check = matches!(&tokens[i].0, Token::Word(w) if w.eq_ignore_ascii_case("CHECK"));
+        if is_check && matches!(tokens.get(i + 1), Some((Token::Punct('('), _, _))) {
+            let start = tokens[i + 1].2;
+            let mut depth = 0;
+            let mut j = i + 1;
+            while j < tokens.len() {
+                match tokens[j].0 {
+                    Token::Punct('(') => depth += 1,
+                    Token::Punct(')') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+                j += 1;
+            }
+            let end = tokens.get(j).map(|t| t.1).unwrap_or(create_sql.len());
+            checks.push(create_sql[start..end].trim().to_string());
+            i = j;
+        }
+        i += 1;
+    }
+
+    checks
+}
+
+/// Whether an SQL expression mentions `column` as an identifier.
+pub fn references_column(expr: &str, column: &str) -> bool {
+    tokenize(expr).iter().any(|(token, _, _)| match token {
+        Token::Word(w) | Token::Quoted(w) => w.eq_ignore_ascii_case(column),
+        _ => false,
+    })
+}
+
+/// Whether a `CREATE TABLE` statement declares an AUTOINCREMENT key.
+pub fn has_autoincrement(create_sql: &str) -> bool {
+    tokenize(create_sql)
+        .iter()
+        .any(|(token, _, _)| matches!(token, Token::Word(w) if w.eq_ignore_ascii_case("AUTOINCREMENT")))
+}
+
+/// Features of a `CREATE TABLE` statement that a [`TableDefinition`] cannot
+/// express, so that rebuilding the table from its definition would drop them.
+pub fn unsupported_table_features(create_sql: &str) -> Vec<&'static str> {
+    let tokens = tokenize(create_sql);
+    let word = |i: usize, expected: &str| {
+        matches!(tokens.get(i), Some((Token::Word(w), _, _)) if w.eq_ignore_ascii_case(expected))
+    };
+    let mut features = Vec::new();
+    let mut depth = 0;
+    // Table options follow the column list, at depth 0 once it has closed
+    let mut after_columns = false;
+
+    for (i, (token, _, _)) in tokens.iter().enumerate() {
+        let feature = match token {
+            Token::Punct('(') => {
+                depth += 1;
+                None
+            }
+            Token::Punct(')') => {
+                depth -= 1;
+                after_columns |= depth == 0;
+                None
+            }
+            // Column and table constraints sit at depth 1; deeper words
+            // belong to CHECK and DEFAULT expressions, which are kept as text
+            Token::Word(_) if depth == 1 => {
+                if word(i, "COLLATE") {
+                    Some("COLLATE")
+                } else if (word(i, "GENERATED") && word(i + 1, "ALWAYS"))
+                    || (word(i, "AS")
+                        && matches!(tokens.get(i + 1), Some((Token::Punct('('), _, _))))
+                {
+                    Some("generated columns")
+                } else if word(i, "ON") && word(i + 1, "CONFLICT") {
+                    Some("ON CONFLICT clauses")
+                } else {
+                    None
+                }
+            }
+            Token::Word(_) if depth == 0 && after_columns => {
+                if word(i, "STRICT") {
+                    Some("STRICT")
+                } else if word(i, "WITHOUT") {
+                    Some("WITHOUT ROWID")
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(feature) = feature {
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        }
+    }
+
+    features
+}
+
+/// The `CREATE TABLE` statement with the table renamed to `name`, keeping
+/// everything else as written. `None` if the statement cannot be parsed.
+pub fn rename_create_table(create_sql: &str, name: &str) -> Option<String> {
+    let tokens = tokenize(create_sql);
+    let is_word = |i: usize, expected: &str| {
+        matches!(tokens.get(i), Some((Token::Word(w), _, _)) if w.eq_ignore_ascii_case(expected))
+    };
+
+    let mut i = (0..tokens.len()).find(|&i| is_word(i, "TABLE"))? + 1;
+    if is_word(i, "IF") && is_word(i + 1, "NOT") && is_word(i + 2, "EXISTS") {
+        i += 3;
+    }
+    let start = tokens.get(i)?.1;
+    // Schema-qualified names are replaced as a whole
+    if matches!(tokens.get(i + 1), Some((Token::Punct('.'), _, _))) {
+        i += 2;
+    }
+    let (token, _, end) = tokens.get(i)?;
+    if !matches!(token, Token::Word(_) | Token::Quoted(_)) {
+        return None;
+    }
+
+    Some(format!(
+        "{}{}{}",
+        &create_sql[..start],
+        quote_ident(name),
+        &create_sql[*end..]
+    ))
+}
@@ -0,0 +1,95 @@
+//! Typed database errors. Adapter methods still return `anyhow::Result`;
+//! errors the caller can act on are raised as `DbError` so tools can report
+//! them with a machine-readable code.
+
+use rusqlite::ErrorCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("Table '{0}' does not exist")]
+    UnknownTable(String),
+    #[error("Column '{table}'.'{column}' does not exist")]
+    UnknownColumn { table: String, column: String },
+    #[error("Index '{0}' does not exist")]
+    UnknownIndex(String),
+    #[error("Table '{0}' already exists")]
+    TableExists(String),
+    #[error("Column '{table}'.'{column}' already exists")]
+    ColumnExists { table: String, column: String },
+    #[error("Table '{0}' is reserved for internal use; start the server with --admin to access it")]
+    ReservedTable(String),
+    /// The operation, e.g. `insert`, was attempted in read-only mode
+    #[error("Cannot {0} in read-only mode")]
+    Readonly(&'static str),
+    #[error("{0}")]
+    ConstraintViolation(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("{0}")]
+    LimitExceeded(String),
+    #[error("Database is busy: {0}")]
+    Busy(String),
+    #[error("Request cancelled")]
+    Cancelled,
+}
+
+impl DbError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DbError::UnknownTable(_) => "unknown_table",
+            DbError::UnknownColumn { .. } => "unknown_column",
+            DbError::UnknownIndex(_) => "unknown_index",
+            DbError::TableExists(_) => "table_exists",
+            DbError::ColumnExists { .. } => "column_exists",
+            DbError::ReservedTable(_) => "reserved_table",
+            DbError::Readonly(_) => "readonly",
+            DbError::ConstraintViolation(_) => "constraint_violation",
+            DbError::InvalidArgument(_) => "invalid_argument",
+            DbError::Unsupported(_) => "unsupported",
+            DbError::LimitExceeded(_) => "limit_exceeded",
+            DbError::Busy(_) => "busy",
+            DbError::Cancelled => "cancelled",
+        }
+    }
+
+    /// Classify a raw SQLite error, if it matches one of the typed cases.
+    pub fn from_sqlite(err: &rusqlite::Error) -> Option<Self> {
+        match err.sqlite_error_code() {
+            Some(ErrorCode::ConstraintViolation) => {
+                Some(DbError::ConstraintViolation(err.to_string()))
+            }
+            Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+                Some(DbError::Busy(err.to_string()))
+            }
+            Some(ErrorCode::ReadOnly) => Some(DbError::Readonly("write")),
+            Some(ErrorCode::OperationInterrupted) => Some(DbError::Cancelled),
+            _ => {
+                let message = match err {
+                    rusqlite::Error::SqliteFailure(_, Some(msg)) => msg,
+                    rusqlite::Error::SqlInputError { msg, .. } => msg,
+                    _ => return None,
+                };
+                message
+                    .strip_prefix("no such table: ")
+                    .map(|table| DbError::UnknownTable(table.to_string()))
+            }
+        }
+    }
+}
+
+/// Machine-readable code for an error returned by a `DatabaseAdapter`: the
+/// code of the first `DbError` in its chain, otherwise one derived from the
+/// underlying SQLite error.
+pub fn error_code(err: &anyhow::Error) -> &'static str {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<DbError>() {
+            return e.code();
+        }
+        if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+            return DbError::from_sqlite(e).map_or("sqlite_error", |e| e.code());
+        }
+    }
+    "internal_error"
+}
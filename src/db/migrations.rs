@@ -0,0 +1,322 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::db::adapter::{
+    AlterTableOperation, ColumnDefinition, IndexDefinition, TableDefinition,
+};
+use crate::db::ddl;
+
+pub const MIGRATIONS_TABLE: &str = "_migrations";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationCommand {
+    /// Report applied and pending migrations
+    Status,
+    /// Apply pending migrations, up to `target` if given
+    Up,
+    /// Revert the latest migration, or every migration above `target` if given
+    Down,
+}
+
+/// One step of a JSON migration file. A file holds an array of these.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum MigrationStep {
+    CreateTable(TableDefinition),
+    AddColumn {
+        table: String,
+        column: ColumnDefinition,
+    },
+    CreateIndex(IndexDefinition),
+    DropIndex {
+        name: String,
+        #[serde(default)]
+        if_exists: bool,
+    },
+    AlterTable {
+        table: String,
+        operation: AlterTableOperation,
+    },
+    Sql {
+        sql: String,
+    },
+}
+
+impl MigrationStep {
+    /// Human readable form of the step, used for dry runs.
+    pub fn describe(&self) -> String {
+        match self {
+            MigrationStep::CreateTable(definition) => {
+                ddl::create_table_sql(definition, &definition.name)
+            }
+            MigrationStep::AddColumn { table, column } => ddl::add_column_sql(table, column),
+            MigrationStep::CreateIndex(index) => ddl::create_index_sql(index),
+            MigrationStep::DropIndex { name, if_exists } => format!(
+                "DROP INDEX {}{}",
+                if *if_exists { "IF EXISTS " } else { "" },
+                ddl::quote_ident(name)
+            ),
+            MigrationStep::AlterTable { table, operation } => format!(
+                "-- alter_table {}: {}",
+                table,
+                serde_json::to_string(operation).unwrap_or_default()
+            ),
+            MigrationStep::Sql { sql } => sql.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MigrationSource {
+    Sql(String),
+    Steps(Vec<MigrationStep>),
+}
+
+impl MigrationSource {
+    pub fn describe(&self) -> Vec<String> {
+        match self {
+            MigrationSource::Sql(sql) => vec![sql.trim().to_string()],
+            MigrationSource::Steps(steps) => steps.iter().map(MigrationStep::describe).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationFile {
+    pub version: i64,
+    pub name: String,
+    pub up: Option<PathBuf>,
+    pub down: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the up file has changed since
+    Modified,
+    /// Applied, but the files are gone
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub state: MigrationState,
+    pub applied_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct MigrationRun {
+    pub version: i64,
+    pub name: String,
+    pub direction: MigrationCommand,
+    pub statements: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct MigrationReport {
+    pub command: MigrationCommand,
+    pub dry_run: bool,
+    pub current_version: Option<i64>,
+    /// Migrations executed, or that would be executed in a dry run
+    pub executed: Vec<MigrationRun>,
+    pub migrations: Vec<MigrationStatus>,
+}
+
+/// Parse `0003_add_users.up.sql` into `(3, "add_users", "up", "sql")`.
+fn parse_file_name(file_name: &str) -> Option<(i64, &str, &str, &str)> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let (stem, direction) = stem.rsplit_once('.')?;
+    let (version, name) = stem.split_once('_')?;
+
+    if !matches!(extension, "sql" | "json") || !matches!(direction, "up" | "down") {
+        return None;
+    }
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((version.parse().ok()?, name, direction, extension))
+}
+
+/// Find migration files in `dir`, ordered by version. Files are named
+/// `<version>_<name>.<up|down>.<sql|json>`; anything else is ignored.
+pub fn discover(dir: &Path) -> anyhow::Result<Vec<MigrationFile>> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| anyhow!("Failed to read migrations directory {:?}: {}", dir, e))?;
+
+    let mut files: BTreeMap<i64, MigrationFile> = BTreeMap::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some((version, name, direction, _)) = parse_file_name(file_name) else {
+            debug!("Ignoring non-migration file {:?}", path);
+            continue;
+        };
+
+        let file = files.entry(version).or_insert_with(|| MigrationFile {
+            version,
+            name: name.to_string(),
+            up: None,
+            down: None,
+        });
+        if file.name != name {
+            return Err(anyhow!(
+                "Migration version {} is used by both '{}' and '{}'",
+                version,
+                file.name,
+                name
+            ));
+        }
+
+        let slot = if direction == "up" {
+            &mut file.up
+        } else {
+            &mut file.down
+        };
+        if slot.is_some() {
+            return Err(anyhow!(
+                "Migration {} has more than one {} file",
+                version,
+                direction
+            ));
+        }
+        *slot = Some(path);
+    }
+
+    Ok(files.into_values().collect())
+}
+
+fn read(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read migration {:?}: {}", path, e))
+}
+
+pub fn checksum(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Read a migration file, returning its contents and checksum.
+pub fn load(path: &Path) -> anyhow::Result<(MigrationSource, String)> {
+    let content = read(path)?;
+    let checksum = checksum(&content);
+
+    let source = if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        let steps: Vec<MigrationStep> = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid JSON migration {:?}: {}", path, e))?;
+        MigrationSource::Steps(steps)
+    } else {
+        MigrationSource::Sql(content)
+    };
+
+    Ok((source, checksum))
+}
+
+pub fn ensure_migrations_table(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS [_migrations] (
+  [version] INTEGER PRIMARY KEY,
+  [name] TEXT NOT NULL,
+  [checksum] TEXT NOT NULL,
+  [applied_at] INTEGER DEFAULT (strftime('%s', 'now'))
+)",
+        [],
+    )
+    .map_err(|e| anyhow!("Failed to create {}: {}", MIGRATIONS_TABLE, e))?;
+    Ok(())
+}
+
+/// Migrations recorded in `_migrations`, ordered by version. Empty if the
+/// table has not been created yet.
+pub fn applied(conn: &rusqlite::Connection) -> anyhow::Result<Vec<AppliedMigration>> {
+    let exists: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?",
+        [MIGRATIONS_TABLE],
+        |row| row.get(0),
+    )?;
+    if exists == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT version, name, checksum, applied_at FROM _migrations ORDER BY version",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                name: row.get(1)?,
+                checksum: row.get(2)?,
+                applied_at: row.get::<_, Option<i64>>(3)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+pub fn status(
+    files: &[MigrationFile],
+    applied: &[AppliedMigration],
+) -> anyhow::Result<Vec<MigrationStatus>> {
+    let mut statuses: BTreeMap<i64, MigrationStatus> = BTreeMap::new();
+
+    for file in files {
+        let state = match applied.iter().find(|a| a.version == file.version) {
+            Some(record) => {
+                let current = match &file.up {
+                    Some(path) => Some(checksum(&read(path)?)),
+                    None => None,
+                };
+                if current.as_deref() == Some(record.checksum.as_str()) {
+                    MigrationState::Applied
+                } else {
+                    MigrationState::Modified
+                }
+            }
+            None => MigrationState::Pending,
+        };
+        statuses.insert(
+            file.version,
+            MigrationStatus {
+                version: file.version,
+                name: file.name.clone(),
+                state,
+                applied_at: applied
+                    .iter()
+                    .find(|a| a.version == file.version)
+                    .map(|a| a.applied_at),
+            },
+        );
+    }
+
+    for record in applied {
+        statuses
+            .entry(record.version)
+            .or_insert_with(|| MigrationStatus {
+                version: record.version,
+                name: record.name.clone(),
+                state: MigrationState::Missing,
+                applied_at: Some(record.applied_at),
+            });
+    }
+
+    Ok(statuses.into_values().collect())
+}
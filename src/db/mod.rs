@@ -1,4 +1,10 @@
 pub mod adapter;
+pub mod cancel;
+pub mod ddl;
+pub mod error;
+pub mod migrations;
+pub mod progress;
+pub mod schema_diff;
 pub mod sqlite;
 
 pub use adapter::{DatabaseAdapter, OrderClause};
@@ -0,0 +1,25 @@
+//! Progress reporting for long-running database operations. The server runs
+//! requests that carry a progress token inside `CURRENT`; operations call
+//! `report` as they go and do nothing when nobody is listening.
+
+use std::sync::Arc;
+
+tokio::task_local! {
+    /// Where progress of the current request is sent.
+    pub static CURRENT: ProgressSink;
+}
+
+/// Receives `(processed, total, message)` updates.
+#[derive(Clone)]
+pub struct ProgressSink(Arc<dyn Fn(usize, usize, String) + Send + Sync>);
+
+impl ProgressSink {
+    pub fn new(f: impl Fn(usize, usize, String) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+/// Report progress of the current request, if it asked for it.
+pub fn report(processed: usize, total: usize, message: impl FnOnce() -> String) {
+    let _ = CURRENT.try_with(|sink| (sink.0)(processed, total, message()));
+}
@@ -0,0 +1,519 @@
+use anyhow::anyhow;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::db::adapter::{ColumnInfo, ForeignKeyInfo, IndexInfo, TableSchema};
+use crate::db::ddl;
+use crate::db::sqlite::{
+    auxiliary_tables_exist, get_table_definition, hidden_rowid, introspect_table, user_tables,
+    AUXILIARY_TABLES_DDL,
+};
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SchemaDiff {
+    /// Tables only present in the target
+    pub added_tables: Vec<TableSchema>,
+    /// Tables only present in the source
+    pub removed_tables: Vec<String>,
+    pub changed_tables: Vec<TableDiff>,
+    /// Statements that turn the source schema into the target schema. The
+    /// transaction is rolled back if it leaves foreign keys violated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migration_sql: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+#[serde(default)]
+pub struct TableDiff {
+    pub table: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_columns: Vec<ColumnInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_columns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_columns: Vec<Change<ColumnInfo>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_foreign_keys: Vec<ForeignKeyInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_foreign_keys: Vec<ForeignKeyInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_indexes: Vec<IndexInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_indexes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_indexes: Vec<Change<IndexInfo>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_triggers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_triggers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_triggers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<CommentChange>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct CommentChange {
+    /// `None` for the table comment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub before: String,
+    pub after: String,
+}
+
+impl TableDiff {
+    fn is_empty(&self) -> bool {
+        self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+            && self.changed_columns.is_empty()
+            && self.added_foreign_keys.is_empty()
+            && self.removed_foreign_keys.is_empty()
+            && self.added_indexes.is_empty()
+            && self.removed_indexes.is_empty()
+            && self.changed_indexes.is_empty()
+            && self.added_triggers.is_empty()
+            && self.removed_triggers.is_empty()
+            && self.changed_triggers.is_empty()
+            && self.comments.is_empty()
+    }
+
+    /// Whether the table has to be rebuilt rather than altered in place:
+    /// anything beyond added columns, explicit indexes, triggers and
+    /// comments is part of the `CREATE TABLE` statement.
+    fn needs_rebuild(&self) -> bool {
+        !self.removed_columns.is_empty()
+            || !self.changed_columns.is_empty()
+            || !self.added_foreign_keys.is_empty()
+            || !self.removed_foreign_keys.is_empty()
+            || self.added_indexes.iter().any(|i| i.origin != "c")
+            || self.changed_indexes.iter().any(|c| c.before.origin != "c" || c.after.origin != "c")
+            || self.removed_indexes.iter().any(|i| i.starts_with("sqlite_autoindex_"))
+    }
+}
+
+/// The comment `get_schema` would report: the stored one, or the name.
+fn effective_desc(desc: &Option<String>, name: &str) -> String {
+    desc.clone().unwrap_or_else(|| name.to_string())
+}
+
+fn diff_table(source: &TableSchema, target: &TableSchema) -> TableDiff {
+    let mut diff = TableDiff {
+        table: target.name.clone(),
+        ..Default::default()
+    };
+
+    let before = effective_desc(&source.desc, &source.name);
+    let after = effective_desc(&target.desc, &target.name);
+    if before != after {
+        diff.comments.push(CommentChange {
+            column: None,
+            before,
+            after,
+        });
+    }
+
+    for column in &target.columns {
+        let Some(old) = source.columns.iter().find(|c| c.name == column.name) else {
+            diff.added_columns.push(column.clone());
+            continue;
+        };
+
+        if old.data_type != column.data_type
+            || old.not_null != column.not_null
+            || old.default_value != column.default_value
+            || old.is_primary_key != column.is_primary_key
+        {
+            diff.changed_columns.push(Change {
+                before: old.clone(),
+                after: column.clone(),
+            });
+        }
+
+        let before = effective_desc(&old.desc, &old.name);
+        let after = effective_desc(&column.desc, &column.name);
+        if before != after {
+            diff.comments.push(CommentChange {
+                column: Some(column.name.clone()),
+                before,
+                after,
+            });
+        }
+    }
+    for column in &source.columns {
+        if !target.columns.iter().any(|c| c.name == column.name) {
+            diff.removed_columns.push(column.name.clone());
+        }
+    }
+
+    for fk in &target.foreign_keys {
+        if !source.foreign_keys.contains(fk) {
+            diff.added_foreign_keys.push(fk.clone());
+        }
+    }
+    for fk in &source.foreign_keys {
+        if !target.foreign_keys.contains(fk) {
+            diff.removed_foreign_keys.push(fk.clone());
+        }
+    }
+
+    for index in &target.indexes {
+        match source.indexes.iter().find(|i| i.name == index.name) {
+            None => diff.added_indexes.push(index.clone()),
+            Some(old) if old != index => diff.changed_indexes.push(Change {
+                before: old.clone(),
+                after: index.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for index in &source.indexes {
+        if !target.indexes.iter().any(|i| i.name == index.name) {
+            diff.removed_indexes.push(index.name.clone());
+        }
+    }
+
+    for trigger in &target.triggers {
+        match source.triggers.iter().find(|t| t.name == trigger.name) {
+            None => diff.added_triggers.push(trigger.name.clone()),
+            Some(old) if old.sql != trigger.sql => diff.changed_triggers.push(trigger.name.clone()),
+            Some(_) => {}
+        }
+    }
+    for trigger in &source.triggers {
+        if !target.triggers.iter().any(|t| t.name == trigger.name) {
+            diff.removed_triggers.push(trigger.name.clone());
+        }
+    }
+
+    diff
+}
+
+/// Compare the schemas of two databases, table by table. With `include_sql`
+/// the statements that migrate `source` to `target` are generated too.
+pub fn diff(
+    source: &rusqlite::Connection,
+    target: &rusqlite::Connection,
+    include_sql: bool,
+) -> anyhow::Result<SchemaDiff> {
+    let source_tables: BTreeSet<String> = user_tables(source)?.into_iter().collect();
+    let target_tables: BTreeSet<String> = user_tables(target)?.into_iter().collect();
+
+    let mut added_tables = Vec::new();
+    for table in target_tables.difference(&source_tables) {
+        added_tables.push(introspect_table(target, table)?);
+    }
+    let removed_tables: Vec<String> = source_tables.difference(&target_tables).cloned().collect();
+
+    let mut changed_tables = Vec::new();
+    for table in source_tables.intersection(&target_tables) {
+        let diff = diff_table(&introspect_table(source, table)?, &introspect_table(target, table)?);
+        if !diff.is_empty() {
+            changed_tables.push(diff);
+        }
+    }
+
+    let mut diff = SchemaDiff {
+        added_tables,
+        removed_tables,
+        changed_tables,
+        migration_sql: None,
+    };
+    if include_sql {
+        diff.migration_sql = Some(migration_sql(&diff, source, target)?);
+    }
+    Ok(diff)
+}
+
+fn object_sql(conn: &rusqlite::Connection, kind: &str, name: &str) -> anyhow::Result<String> {
+    conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type=? AND name=?",
+        [kind, name],
+        |row| row.get(0),
+    )
+    .map_err(|e| anyhow!("Failed to read definition of {} '{}': {}", kind, name, e))
+}
+
+/// `CREATE INDEX` and `CREATE TRIGGER` statements for everything attached to
+/// `table` in `conn`. Automatic indexes have no SQL and are skipped.
+fn table_objects_sql(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT sql FROM sqlite_master WHERE tbl_name = ? AND type IN ('index', 'trigger') AND sql IS NOT NULL ORDER BY type, name",
+    )?;
+    let statements = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(statements)
+}
+
+fn table_comment_sql(table: &str, desc: &str) -> String {
+    format!(
+        "INSERT OR REPLACE INTO [_table_comment] ([table_name], [table_desc]) VALUES ({}, {})",
+        ddl::quote_literal(table),
+        ddl::quote_literal(desc)
+    )
+}
+
+fn column_comment_sql(table: &str, column: &str, desc: &str) -> String {
+    format!(
+        "INSERT INTO [_table_column_comment] ([table_name], [column_name], [column_desc]) VALUES ({}, {}, {}) ON CONFLICT ([table_name], [column_name]) DO UPDATE SET [column_desc] = excluded.[column_desc]",
+        ddl::quote_literal(table),
+        ddl::quote_literal(column),
+        ddl::quote_literal(desc)
+    )
+}
+
+/// Statements that rebuild `table` in the source with its definition from
+/// the target, keeping the rows of the columns both sides have in common.
+/// The target's own `CREATE TABLE` text is reused, so collations, generated
+/// columns and table options come across as written.
+fn rebuild_sql(
+    source: &rusqlite::Connection,
+    target: &rusqlite::Connection,
+    table: &str,
+    common_columns: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let temp_name = format!("_rebuild_{}", table);
+    let create_sql = object_sql(target, "table", table)?;
+    let create_sql = ddl::rename_create_table(&create_sql, &temp_name)
+        .ok_or_else(|| anyhow!("Failed to parse definition of table '{}'", table))?;
+
+    let mut selected: Vec<String> = common_columns.iter().map(|c| ddl::quote_ident(c)).collect();
+    let mut inserted = selected.clone();
+    // Rows are keyed on rowid where there is no INTEGER PRIMARY KEY
+    if let (Some(from), Some(to)) = (hidden_rowid(source, table)?, hidden_rowid(target, table)?) {
+        selected.insert(0, from.to_string());
+        inserted.insert(0, to.to_string());
+    }
+
+    let mut statements = vec![create_sql];
+    if !selected.is_empty() {
+        statements.push(format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            ddl::quote_ident(&temp_name),
+            inserted.join(", "),
+            selected.join(", "),
+            ddl::quote_ident(table)
+        ));
+    }
+    statements.extend([
+        format!("DROP TABLE {}", ddl::quote_ident(table)),
+        "PRAGMA legacy_alter_table = ON".to_string(),
+        format!(
+            "ALTER TABLE {} RENAME TO {}",
+            ddl::quote_ident(&temp_name),
+            ddl::quote_ident(table)
+        ),
+        "PRAGMA legacy_alter_table = OFF".to_string(),
+    ]);
+    statements.extend(table_objects_sql(target, table)?);
+    Ok(statements)
+}
+
+fn migration_sql(
+    diff: &SchemaDiff,
+    source: &rusqlite::Connection,
+    target: &rusqlite::Connection,
+) -> anyhow::Result<Vec<String>> {
+    let mut statements = Vec::new();
+    let mut comments = Vec::new();
+    // Stale comment rows only need removing if the source stores comments
+    let source_has_comments = auxiliary_tables_exist(source)?;
+
+    for table in &diff.removed_tables {
+        statements.push(format!("DROP TABLE {}", ddl::quote_ident(table)));
+        if !source_has_comments {
+            continue;
+        }
+        comments.push(format!(
+            "DELETE FROM [_table_comment] WHERE [table_name] = {}",
+            ddl::quote_literal(table)
+        ));
+        comments.push(format!(
+            "DELETE FROM [_table_column_comment] WHERE [table_name] = {}",
+            ddl::quote_literal(table)
+        ));
+    }
+
+    for schema in &diff.added_tables {
+        statements.push(object_sql(target, "table", &schema.name)?);
+        statements.extend(table_objects_sql(target, &schema.name)?);
+        if let Some(desc) = &schema.desc {
+            comments.push(table_comment_sql(&schema.name, desc));
+        }
+        for column in &schema.columns {
+            if let Some(desc) = &column.desc {
+                comments.push(column_comment_sql(&schema.name, &column.name, desc));
+            }
+        }
+    }
+
+    for table in &diff.changed_tables {
+        let name = &table.table;
+
+        // Added columns are declared from the target's definition, which
+        // cannot express everything; rebuild from its SQL instead
+        let lossy_columns = !table.added_columns.is_empty()
+            && !ddl::unsupported_table_features(&object_sql(target, "table", name)?).is_empty();
+        if table.needs_rebuild() || lossy_columns {
+            let target_columns = introspect_table(target, name)?.columns;
+            let common: Vec<String> = target_columns
+                .iter()
+                .map(|c| c.name.clone())
+                .filter(|c| !table.added_columns.iter().any(|a| &a.name == c))
+                .collect();
+            statements.extend(rebuild_sql(source, target, name, &common)?);
+        } else {
+            if !table.added_columns.is_empty() {
+                let definition = get_table_definition(target, name)?;
+                for column in &table.added_columns {
+                    if let Some(column) = definition.columns.iter().find(|c| c.name == column.name) {
+                        statements.push(ddl::add_column_sql(name, column));
+                    }
+                }
+            }
+
+            let dropped = table
+                .removed_indexes
+                .iter()
+                .chain(table.changed_indexes.iter().map(|c| &c.after.name));
+            for index in dropped {
+                statements.push(format!("DROP INDEX {}", ddl::quote_ident(index)));
+            }
+            let created = table
+                .added_indexes
+                .iter()
+                .chain(table.changed_indexes.iter().map(|c| &c.after));
+            for index in created {
+                statements.push(object_sql(target, "index", &index.name)?);
+            }
+
+            let dropped = table.removed_triggers.iter().chain(&table.changed_triggers);
+            for trigger in dropped {
+                statements.push(format!("DROP TRIGGER {}", ddl::quote_ident(trigger)));
+            }
+            for trigger in table.added_triggers.iter().chain(&table.changed_triggers) {
+                statements.push(object_sql(target, "trigger", trigger)?);
+            }
+        }
+
+        for column in table.removed_columns.iter().filter(|_| source_has_comments) {
+            comments.push(format!(
+                "DELETE FROM [_table_column_comment] WHERE [table_name] = {} AND [column_name] = {}",
+                ddl::quote_literal(name),
+                ddl::quote_literal(column)
+            ));
+        }
+        for column in &table.added_columns {
+            if let Some(desc) = &column.desc {
+                comments.push(column_comment_sql(name, &column.name, desc));
+            }
+        }
+        for change in &table.comments {
+            comments.push(match &change.column {
+                Some(column) => column_comment_sql(name, column, &change.after),
+                None => table_comment_sql(name, &change.after),
+            });
+        }
+    }
+
+    if statements.is_empty() && comments.is_empty() {
+        return Ok(statements);
+    }
+
+    if !comments.is_empty() {
+        statements.extend(AUXILIARY_TABLES_DDL.iter().map(|s| s.to_string()));
+        statements.extend(comments);
+    }
+
+    // Same procedure as alter_table's rebuild: foreign keys are checked once
+    // every table is in its final shape. A script cannot act on the rows of
+    // PRAGMA foreign_key_check, so a CHECK constraint turns any violation
+    // into a ROLLBACK, after which the COMMIT fails harmlessly.
+    let mut script = vec![
+        "PRAGMA foreign_keys = OFF".to_string(),
+        "BEGIN".to_string(),
+    ];
+    script.extend(statements);
+    script.extend([
+        "CREATE TEMP TABLE _fk_guard (violations INTEGER CHECK (violations = 0))".to_string(),
+        "INSERT OR ROLLBACK INTO _fk_guard SELECT count(*) FROM pragma_foreign_key_check"
+            .to_string(),
+        "DROP TABLE temp._fk_guard".to_string(),
+        "COMMIT".to_string(),
+        "PRAGMA foreign_keys = ON".to_string(),
+    ]);
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(sql: &str) -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn
+    }
+
+    fn migrate(
+        source: &rusqlite::Connection,
+        target: &rusqlite::Connection,
+    ) -> rusqlite::Result<()> {
+        let script = diff(source, target, true).unwrap().migration_sql.unwrap();
+        source.execute_batch(&script.join(";\n"))
+    }
+
+    #[test]
+    fn rebuild_keeps_rowids_and_target_definition() {
+        let source = database(
+            "CREATE TABLE n (a TEXT, b INTEGER);
+             INSERT INTO n VALUES ('x', 1), ('y', 2), ('z', 3);
+             DELETE FROM n WHERE b = 1;",
+        );
+        let create = "CREATE TABLE n (a TEXT COLLATE NOCASE, b REAL, \
+                      c REAL GENERATED ALWAYS AS (b * 2) VIRTUAL) STRICT";
+        let target = database(create);
+
+        migrate(&source, &target).unwrap();
+
+        let rows: Vec<(i64, String, f64)> = source
+            .prepare("SELECT rowid, a, c FROM n WHERE a IN ('Y', 'Z') ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![(2, "y".to_string(), 4.0), (3, "z".to_string(), 6.0)]
+        );
+        assert_eq!(
+            ddl::unsupported_table_features(&object_sql(&source, "table", "n").unwrap()),
+            ["COLLATE", "generated columns", "STRICT"]
+        );
+    }
+
+    #[test]
+    fn foreign_key_violations_roll_back() {
+        let source = database(
+            "CREATE TABLE parent (id INTEGER PRIMARY KEY);
+             CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER);
+             INSERT INTO child VALUES (1, 99);",
+        );
+        let target = database(
+            "CREATE TABLE parent (id INTEGER PRIMARY KEY);
+             CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER REFERENCES parent(id));",
+        );
+
+        assert!(migrate(&source, &target).is_err());
+        assert!(source.is_autocommit());
+        let definition = get_table_definition(&source, "child").unwrap();
+        assert!(definition.foreign_keys.is_empty());
+    }
+}
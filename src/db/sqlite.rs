@@ -1,16 +1,95 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
 use tracing::{debug, info};
 
 use crate::db::adapter::{
-    BatchResult, ColumnInfo, DatabaseAdapter, FilterOperators, FilterValue, OrderClause,
-    OrderDirection, QueryFilter, TableSchema,
+    AlterStrategy, AlterTableOperation, AlterTableResult, BatchResult, ColumnDefinition,
+    ColumnInfo, ColumnMetadata, CommentDocument, ConstraintDefinition, DataChange,
+    DatabaseAdapter, FilterOperators, FilterValue, ForeignKeyAction, ForeignKeyDefinition,
+    ForeignKeyInfo, ImportCommentsResult, IndexDefinition, IndexInfo, OrderClause,
+    OrderDirection, QueryFilter, TableDefinition, TableSchema, TriggerInfo,
 };
+use crate::db::cancel::CancelToken;
+use crate::db::ddl;
+use crate::db::error::DbError;
+use crate::db::migrations::{
+    self, AppliedMigration, MigrationCommand, MigrationReport, MigrationRun, MigrationSource,
+    MigrationState, MigrationStep,
+};
+use crate::db::progress;
+use crate::db::schema_diff::{self, SchemaDiff};
 
+/// DDL for the tables that hold table and column comments.
+pub(crate) const AUXILIARY_TABLES_DDL: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS [_table_comment] (
+  [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+  [table_name] TEXT NOT NULL,
+  [table_desc] TEXT NOT NULL,
+  [ctime] INTEGER DEFAULT (strftime('%s', 'now')),
+  [utime] INTEGER DEFAULT (strftime('%s', 'now')),
+  UNIQUE ([table_name])
+)",
+    "CREATE TABLE IF NOT EXISTS [_table_column_comment] (
+  [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+  [table_name] TEXT NOT NULL,
+  [column_name] TEXT NOT NULL,
+  [column_desc] TEXT,
+  [unit] TEXT,
+  [allowed_values] TEXT,
+  [examples] TEXT,
+  [format] TEXT,
+  [sensitivity] TEXT,
+  [deprecated] INTEGER NOT NULL DEFAULT 0
+)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS [idx_table_column_unique] ON [_table_column_comment] ([table_name], [column_name])",
+];
+
+/// Structured metadata columns of `_table_column_comment`, added to tables
+/// created before they existed.
+const COLUMN_METADATA_COLUMNS: [(&str, &str); 6] = [
+    ("unit", "TEXT"),
+    ("allowed_values", "TEXT"),
+    ("examples", "TEXT"),
+    ("format", "TEXT"),
+    ("sensitivity", "TEXT"),
+    ("deprecated", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+/// Placeholder returned in place of values of PII and secret columns.
+const MASKED_VALUE: &str = "***";
+
+/// Tables managed by the server itself rather than holding user data.
+const INTERNAL_TABLES: [&str; 4] = [
+    "_table_comment",
+    "_table_column_comment",
+    migrations::MIGRATIONS_TABLE,
+    "_audit",
+];
+
+/// Whether `table` is in the reserved namespace of tables the server manages
+/// itself. Generic CRUD and DDL tools only touch these in admin mode.
+pub fn is_internal_table(table: &str) -> bool {
+    INTERNAL_TABLES
+        .iter()
+        .any(|internal| internal.eq_ignore_ascii_case(table))
+}
+
+/// How often `PRAGMA data_version` is polled to detect commits made by
+/// other processes.
+const DATA_VERSION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
 pub struct SqliteDatabase {
     conn: Arc<Mutex<rusqlite::Connection>>,
+    interrupt: Arc<rusqlite::InterruptHandle>,
     readonly: bool,
+    admin: bool,
+    name: String,
+    changes: broadcast::Sender<DataChange>,
 }
 
 impl SqliteDatabase {
@@ -24,12 +103,75 @@ impl SqliteDatabase {
         // Enable foreign keys
         conn.execute("PRAGMA foreign_keys = ON", [])?;
 
+        let (changes, _) = broadcast::channel(256);
+        register_change_hooks(&conn, changes.clone())?;
+
+        let interrupt = Arc::new(conn.get_interrupt_handle());
+        let conn = Arc::new(Mutex::new(conn));
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(poll_data_version(Arc::downgrade(&conn), changes.clone()));
+        }
+
         Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
+            conn,
+            interrupt,
             readonly,
+            admin: false,
+            name: path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("main")
+                .to_string(),
+            changes,
         })
     }
 
+    /// Allow the generic CRUD and DDL methods to touch internal tables.
+    pub fn with_admin(mut self, admin: bool) -> Self {
+        self.admin = admin;
+        self
+    }
+
+    /// Run `f` against the connection on the blocking thread pool, so that
+    /// SQLite work and waiting for the connection lock never stall the async
+    /// runtime. Cancelling the calling request interrupts `f`, events logged
+    /// by `f` stay in the caller's span, and progress it reports reaches the
+    /// caller's client.
+    async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self, &rusqlite::Connection) -> anyhow::Result<T> + Send + 'static,
+    {
+        let db = self.clone();
+        let cancel = CancelToken::current();
+        let span = tracing::Span::current();
+        let progress = progress::CURRENT.try_with(Clone::clone).ok();
+        tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let conn = db
+                .conn
+                .lock()
+                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+            let f = || match cancel {
+                Some(cancel) => cancel.run_interruptible(&db.interrupt, || f(&db, &conn)),
+                None => f(&db, &conn),
+            };
+            match progress {
+                Some(progress) => progress::CURRENT.sync_scope(progress, f),
+                None => f(),
+            }
+        })
+        .await
+        .map_err(|e| anyhow!("Database task failed: {}", e))?
+    }
+
+    fn check_table_access(&self, table: &str) -> anyhow::Result<()> {
+        if is_internal_table(table) && !self.admin {
+            return Err(DbError::ReservedTable(table.to_string()).into());
+        }
+        Ok(())
+    }
+
     fn ensure_auxiliary_tables_exist(&self, conn: &rusqlite::Connection) -> anyhow::Result<bool> {
         let table_comment_exists: i64 = conn
             .query_row(
@@ -48,45 +190,19 @@ impl SqliteDatabase {
         if table_comment_exists == 0 || column_comment_exists == 0 {
             info!("Creating auxiliary tables for comments");
 
-            let tx = conn.unchecked_transaction().map_err(|e| {
-                anyhow!(
-                    "Failed to begin transaction for creating auxiliary tables: {}",
-                    e
-                )
-            })?;
-
-            let sql_statements = vec![
-                "CREATE TABLE IF NOT EXISTS [_table_comment] (
-  [id] INTEGER PRIMARY KEY AUTOINCREMENT,
-  [table_name] TEXT NOT NULL,
-  [table_desc] TEXT NOT NULL,
-  [ctime] INTEGER DEFAULT (strftime('%s', 'now')),
-  [utime] INTEGER DEFAULT (strftime('%s', 'now')),
-  UNIQUE ([table_name])
-)",
-                "CREATE TABLE IF NOT EXISTS [_table_column_comment] (
-  [id] INTEGER PRIMARY KEY AUTOINCREMENT,
-  [table_name] TEXT NOT NULL,
-  [column_name] TEXT NOT NULL,
-  [column_desc] TEXT
-)",
-                "CREATE UNIQUE INDEX IF NOT EXISTS [idx_table_column_unique] ON [_table_column_comment] ([table_name], [column_name])"
-            ];
-
-            for (i, stmt) in sql_statements.iter().enumerate() {
-                debug!("Executing DDL statement {}: {}", i, stmt);
-                let result = tx
-                    .execute(stmt, [])
-                    .map_err(|e| anyhow!("Failed to execute DDL statement {}: {}", i, e))?;
-                debug!("DDL statement {} affected {} rows", i, result);
-            }
-
-            tx.commit().map_err(|e| {
-                anyhow!(
-                    "Failed to commit transaction for creating auxiliary tables: {}",
-                    e
-                )
-            })?;
+            // A savepoint rather than a transaction, so this also works when
+            // called inside a caller's transaction (e.g. a migration)
+            with_savepoint(conn, "create_auxiliary_tables", |conn| {
+                for (i, stmt) in AUXILIARY_TABLES_DDL.iter().enumerate() {
+                    debug!(sql = %stmt, "Executing DDL statement {}", i);
+                    let result = conn
+                        .execute(stmt, [])
+                        .map_err(|e| anyhow!("Failed to execute DDL statement {}: {}", i, e))?;
+                    debug!("DDL statement {} affected {} rows", i, result);
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("Failed to create auxiliary tables: {}", e))?;
 
             info!("Auxiliary tables created successfully");
 
@@ -114,195 +230,989 @@ impl SqliteDatabase {
         ).map_err(|e| anyhow!("Failed to create index on _table_column_comment: {}", e))?;
 
         debug!("Index on _table_column_comment ensured");
+
+        let existing = get_column_names(conn, "_table_column_comment")?;
+        for (column, definition) in COLUMN_METADATA_COLUMNS {
+            if !existing.iter().any(|c| c == column) {
+                info!("Adding column '{}' to _table_column_comment", column);
+                conn.execute(
+                    &format!(
+                        "ALTER TABLE [_table_column_comment] ADD COLUMN [{}] {}",
+                        column, definition
+                    ),
+                    [],
+                )
+                .map_err(|e| anyhow!("Failed to add column '{}' to _table_column_comment: {}", column, e))?;
+            }
+        }
+
         Ok(false)
     }
 
-    fn initialize_default_table_comment(
+    fn table_exists(&self, conn: &rusqlite::Connection, table: &str) -> anyhow::Result<bool> {
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(|e| anyhow!("Failed to check if table '{}' exists: {}", table, e))?;
+
+        Ok(count > 0)
+    }
+
+    fn column_exists(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+        column: &str,
+    ) -> anyhow::Result<bool> {
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name=?",
+                [table, column],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to check if column '{}'.'{}' exists: {}",
+                    table,
+                    column,
+                    e
+                )
+            })?;
+
+        Ok(count > 0)
+    }
+
+    fn set_table_comment_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+        desc: &str,
+    ) -> anyhow::Result<()> {
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
+        }
+
+        self.ensure_auxiliary_tables_exist(conn)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO _table_comment (table_name, table_desc) VALUES (?, ?)",
+            [table, desc],
+        )
+        .with_context(|| format!("Failed to set table comment for '{}'", table))?;
+
+        info!("Set comment for table '{}': {}", table, desc);
+        Ok(())
+    }
+
+    fn set_column_comment_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+        column: &str,
+        desc: &str,
+    ) -> anyhow::Result<()> {
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
+        }
+
+        if !self.column_exists(conn, table, column)? {
+            return Err(DbError::UnknownColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            }
+            .into());
+        }
+
+        self.ensure_auxiliary_tables_exist(conn)?;
+
+        // Upsert rather than replace, so the column's metadata is kept
+        conn.execute(
+            "INSERT INTO _table_column_comment (table_name, column_name, column_desc) VALUES (?, ?, ?)
+             ON CONFLICT (table_name, column_name) DO UPDATE SET column_desc = excluded.column_desc",
+            [table, column, desc],
+        ).with_context(|| format!("Failed to set column comment for '{}'.'{}'", table, column))?;
+
+        info!("Set comment for column '{}.'{}': {}", table, column, desc);
+        Ok(())
+    }
+
+    fn set_column_metadata_internal(
         &self,
         conn: &rusqlite::Connection,
         table: &str,
+        column: &str,
+        metadata: &ColumnMetadata,
     ) -> anyhow::Result<()> {
-        let sql = "INSERT OR IGNORE INTO _table_comment (table_name, table_desc) VALUES (?, ?)";
-        conn.execute(sql, [table, table]).map_err(|e| {
-            anyhow!(
-                "Failed to initialize default table comment for '{}': {}",
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
+        }
+
+        if !self.column_exists(conn, table, column)? {
+            return Err(DbError::UnknownColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            }
+            .into());
+        }
+
+        if let Some(allowed) = &metadata.allowed_values {
+            if allowed.is_empty() {
+                return Err(DbError::InvalidArgument(
+                    "allowed_values must not be empty".to_string(),
+                )
+                .into());
+            }
+            if let Some(example) = metadata
+                .examples
+                .iter()
+                .flatten()
+                .find(|e| !e.is_null() && !allowed.contains(e))
+            {
+                return Err(DbError::InvalidArgument(format!(
+                        "Example {} is not one of the allowed values",
+                        example
+                    ))
+                    .into());
+            }
+        }
+
+        self.ensure_auxiliary_tables_exist(conn)?;
+
+        let to_json = |values: &Option<Vec<serde_json::Value>>| {
+            values
+                .as_ref()
+                .map(|v| serde_json::Value::Array(v.clone()).to_string())
+        };
+        let to_name = |value: serde_json::Result<serde_json::Value>| {
+            value.ok().and_then(|v| v.as_str().map(str::to_string))
+        };
+
+        conn.execute(
+            "INSERT INTO _table_column_comment
+               (table_name, column_name, column_desc, unit, allowed_values, examples, format, sensitivity, deprecated)
+             VALUES (?1, ?2, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (table_name, column_name) DO UPDATE SET
+               unit = excluded.unit,
+               allowed_values = excluded.allowed_values,
+               examples = excluded.examples,
+               format = excluded.format,
+               sensitivity = excluded.sensitivity,
+               deprecated = excluded.deprecated",
+            rusqlite::params![
                 table,
-                e
+                column,
+                metadata.unit,
+                to_json(&metadata.allowed_values),
+                to_json(&metadata.examples),
+                metadata.format.and_then(|f| to_name(serde_json::to_value(f))),
+                metadata.sensitivity.and_then(|s| to_name(serde_json::to_value(s))),
+                metadata.deprecated,
+            ],
+        )
+        .with_context(|| format!("Failed to set column metadata for '{}'.'{}'", table, column))?;
+
+        info!("Set metadata for column '{}.'{}': {:?}", table, column, metadata);
+        Ok(())
+    }
+
+    fn import_comments_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        document: &CommentDocument,
+    ) -> anyhow::Result<ImportCommentsResult> {
+        let mut result = ImportCommentsResult::default();
+
+        let total = document.len();
+        for (index, (table, comments)) in document.iter().enumerate() {
+            progress::report(index, total, || {
+                format!("Importing comments for '{}' ({} of {})", table, index + 1, total)
+            });
+            if !self.table_exists(conn, table)? {
+                debug!("Skipping comments for unknown table '{}'", table);
+                result.unknown_tables.push(table.clone());
+                continue;
+            }
+
+            if let Some(desc) = &comments.desc {
+                self.set_table_comment_internal(conn, table, desc)?;
+                result.tables_updated += 1;
+            }
+
+            for (column, desc) in &comments.columns {
+                if !self.column_exists(conn, table, column)? {
+                    debug!("Skipping comment for unknown column '{}.{}'", table, column);
+                    result.unknown_columns.push(format!("{}.{}", table, column));
+                    continue;
+                }
+                self.set_column_comment_internal(conn, table, column, desc)?;
+                result.columns_updated += 1;
+            }
+        }
+        progress::report(total, total, || format!("Imported comments for {} tables", total));
+
+        Ok(result)
+    }
+
+    fn get_schema_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+    ) -> anyhow::Result<TableSchema> {
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
+        }
+
+        // Read only: comment rows are only written by the set_* calls
+        let schema = introspect_table(conn, table)?;
+        if schema.primary_key.is_none() {
+            return Err(DbError::Unsupported(
+                "Composite primary keys not supported".to_string(),
             )
+            .into());
+        }
+
+        // Tables and columns without a comment are described by their name
+        Ok(TableSchema {
+            desc: schema.desc.or_else(|| Some(table.to_string())),
+            columns: schema
+                .columns
+                .into_iter()
+                .map(|column| ColumnInfo {
+                    desc: column.desc.or_else(|| Some(column.name.clone())),
+                    ..column
+                })
+                .collect(),
+            ..schema
+        })
+    }
+
+    fn create_table_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        definition: &TableDefinition,
+    ) -> anyhow::Result<()> {
+        ddl::validate_table_definition(definition)?;
+
+        if self.table_exists(conn, &definition.name)? {
+            if definition.if_not_exists {
+                debug!("Table '{}' already exists, skipping", definition.name);
+                return Ok(());
+            }
+            return Err(DbError::TableExists(definition.name.to_string()).into());
+        }
+
+        let has_comments =
+            definition.desc.is_some() || definition.columns.iter().any(|c| c.desc.is_some());
+        if has_comments {
+            self.ensure_auxiliary_tables_exist(conn)?;
+        }
+
+        let sql = ddl::create_table_sql(definition, &definition.name);
+        debug!("Creating table '{}'", definition.name);
+        debug!(%sql, "Executing SQL");
+
+        with_savepoint(conn, "create_table", |conn| {
+            conn.execute(&sql, [])
+                .with_context(|| format!("Failed to create table '{}'", definition.name))?;
+
+            if let Some(desc) = &definition.desc {
+                self.set_table_comment_internal(conn, &definition.name, desc)?;
+            }
+            for column in &definition.columns {
+                if let Some(desc) = &column.desc {
+                    self.set_column_comment_internal(conn, &definition.name, &column.name, desc)?;
+                }
+            }
+            Ok(())
         })?;
-        debug!("Initialized default comment for table '{}'", table);
+
+        info!("Created table '{}'", definition.name);
         Ok(())
     }
 
-    fn initialize_default_column_comments(
+    fn add_column_internal(
         &self,
         conn: &rusqlite::Connection,
         table: &str,
-        columns: &[ColumnInfo],
+        column: &ColumnDefinition,
     ) -> anyhow::Result<()> {
-        for column in columns {
-            let sql = "INSERT OR IGNORE INTO _table_column_comment (table_name, column_name, column_desc) VALUES (?, ?, ?)";
-            conn.execute(sql, [table, &column.name, &column.name])
-                .map_err(|e| {
-                    anyhow!(
-                        "Failed to initialize default column comment for '{}.{}': {}",
-                        table,
-                        column.name,
-                        e
-                    )
-                })?;
+        ddl::validate_column_definition(column)?;
+
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
         }
-        debug!(
-            "Initialized default comments for {} columns in table '{}'",
-            columns.len(),
-            table
-        );
+        if self.column_exists(conn, table, &column.name)? {
+            return Err(DbError::ColumnExists {
+                table: table.to_string(),
+                column: column.name.clone(),
+            }
+            .into());
+        }
+        if column.primary_key || column.unique {
+            return Err(DbError::Unsupported(
+                "Cannot add a PRIMARY KEY or UNIQUE column to an existing table".to_string(),
+            )
+            .into());
+        }
+
+        if column.desc.is_some() {
+            self.ensure_auxiliary_tables_exist(conn)?;
+        }
+
+        let sql = ddl::add_column_sql(table, column);
+        debug!("Adding column '{}' to table '{}'", column.name, table);
+        debug!(%sql, "Executing SQL");
+
+        with_savepoint(conn, "add_column", |conn| {
+            conn.execute(&sql, []).with_context(|| {
+                format!("Failed to add column '{}' to table '{}'", column.name, table)
+            })?;
+
+            if let Some(desc) = &column.desc {
+                self.set_column_comment_internal(conn, table, &column.name, desc)?;
+            }
+            Ok(())
+        })?;
+
+        info!("Added column '{}' to table '{}'", column.name, table);
         Ok(())
     }
 
-    fn get_table_comment(
+    fn create_index_internal(
         &self,
         conn: &rusqlite::Connection,
-        table: &str,
+        index: &IndexDefinition,
+    ) -> anyhow::Result<()> {
+        ddl::validate_index_definition(index)?;
+
+        if !self.table_exists(conn, &index.table)? {
+            return Err(DbError::UnknownTable(index.table.to_string()).into());
+        }
+        for column in &index.columns {
+            if !self.column_exists(conn, &index.table, column)? {
+                return Err(DbError::UnknownColumn {
+                    table: index.table.to_string(),
+                    column: column.to_string(),
+                }
+                .into());
+            }
+        }
+
+        let sql = ddl::create_index_sql(index);
+        debug!("Creating index '{}' on table '{}'", index.name, index.table);
+        debug!(%sql, "Executing SQL");
+
+        conn.execute(&sql, [])
+            .with_context(|| format!("Failed to create index '{}'", index.name))?;
+
+        info!("Created index '{}' on table '{}'", index.name, index.table);
+        Ok(())
+    }
+
+    fn drop_index_internal(
+        &self,
+        conn: &rusqlite::Connection,
+        name: &str,
+        if_exists: bool,
     ) -> anyhow::Result<Option<String>> {
         let result = conn.query_row(
-            "SELECT table_desc FROM _table_comment WHERE table_name = ?",
-            [table],
+            "SELECT tbl_name FROM sqlite_master WHERE type='index' AND name=?",
+            [name],
             |row| row.get::<_, String>(0),
         );
 
-        match result {
-            Ok(desc) => {
-                debug!("Retrieved table comment for '{}': {}", table, desc);
-                Ok(Some(desc))
-            }
+        let table = match result {
+            Ok(table) => table,
             Err(rusqlite::Error::QueryReturnedNoRows) => {
-                debug!("No table comment found for '{}'", table);
-                Ok(None)
+                if if_exists {
+                    debug!("Index '{}' does not exist, skipping", name);
+                    return Ok(None);
+                }
+                return Err(DbError::UnknownIndex(name.to_string()).into());
             }
-            Err(e) => Err(anyhow!(
-                "Failed to query table comment for '{}': {}",
-                table,
-                e
-            )),
+            Err(e) => return Err(anyhow!("Failed to look up index '{}': {}", name, e)),
+        };
+
+        let sql = format!("DROP INDEX {}", ddl::quote_ident(name));
+        debug!(%sql, "Executing SQL");
+        conn.execute(&sql, [])
+            .with_context(|| format!("Failed to drop index '{}'", name))?;
+
+        info!("Dropped index '{}' on table '{}'", name, table);
+        Ok(Some(table))
+    }
+
+    /// Run `f` in a transaction with foreign key enforcement switched off, as
+    /// required by SQLite's table rebuild procedure. Violations introduced by
+    /// `f` are detected with `PRAGMA foreign_key_check` before committing.
+    fn with_foreign_keys_disabled<T>(
+        &self,
+        conn: &rusqlite::Connection,
+        f: impl FnOnce(&rusqlite::Connection) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let fk_enabled: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+        conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+
+        let result = (|| {
+            let tx = conn.unchecked_transaction()?;
+            let value = f(&tx)?;
+            check_foreign_keys(&tx)?;
+            tx.commit()?;
+            Ok(value)
+        })();
+
+        if fk_enabled {
+            conn.execute_batch("PRAGMA foreign_keys = ON")?;
         }
+        result
     }
 
-    fn get_column_comment(
+    fn alter_table_internal(
         &self,
         conn: &rusqlite::Connection,
         table: &str,
-        column: &str,
-    ) -> anyhow::Result<Option<String>> {
-        let result = conn.query_row(
-            "SELECT column_desc FROM _table_column_comment WHERE table_name = ? AND column_name = ?",
-            [table, column],
-            |row| row.get::<_, String>(0),
-        );
+        operation: &AlterTableOperation,
+    ) -> anyhow::Result<(String, AlterStrategy, Vec<String>)> {
+        if !self.table_exists(conn, table)? {
+            return Err(DbError::UnknownTable(table.to_string()).into());
+        }
 
-        match result {
-            Ok(desc) => {
-                debug!(
-                    "Retrieved column comment for '{}.{}': {}",
-                    table, column, desc
+        let has_comments = auxiliary_tables_exist(conn)?;
+
+        match operation {
+            AlterTableOperation::RenameTable { new_name } => {
+                if self.table_exists(conn, new_name)? {
+                    return Err(DbError::TableExists(new_name.to_string()).into());
+                }
+                let sql = format!(
+                    "ALTER TABLE {} RENAME TO {}",
+                    ddl::quote_ident(table),
+                    ddl::quote_ident(new_name)
                 );
-                Ok(Some(desc))
+                debug!(%sql, "Executing SQL");
+                conn.execute(&sql, [])?;
+
+                if has_comments {
+                    conn.execute(
+                        "UPDATE _table_comment SET table_name = ? WHERE table_name = ?",
+                        [new_name, table],
+                    )?;
+                    conn.execute(
+                        "UPDATE _table_column_comment SET table_name = ? WHERE table_name = ?",
+                        [new_name, table],
+                    )?;
+                }
+
+                info!("Renamed table '{}' to '{}'", table, new_name);
+                Ok((new_name.clone(), AlterStrategy::Native, Vec::new()))
             }
-            Err(rusqlite::Error::QueryReturnedNoRows) => {
-                debug!("No column comment found for '{}.{}'", table, column);
-                Ok(None)
+            AlterTableOperation::RenameColumn { column, new_name } => {
+                if !self.column_exists(conn, table, column)? {
+                    return Err(DbError::UnknownColumn {
+                        table: table.to_string(),
+                        column: column.to_string(),
+                    }
+                    .into());
+                }
+                if self.column_exists(conn, table, new_name)? {
+                    return Err(DbError::ColumnExists {
+                        table: table.to_string(),
+                        column: new_name.to_string(),
+                    }
+                    .into());
+                }
+                let sql = format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    ddl::quote_ident(table),
+                    ddl::quote_ident(column),
+                    ddl::quote_ident(new_name)
+                );
+                debug!(%sql, "Executing SQL");
+                conn.execute(&sql, [])?;
+
+                if has_comments {
+                    conn.execute(
+                        "UPDATE _table_column_comment SET column_name = ? WHERE table_name = ? AND column_name = ?",
+                        [new_name, table, column],
+                    )?;
+                }
+
+                info!("Renamed column '{}.{}' to '{}'", table, column, new_name);
+                Ok((table.to_string(), AlterStrategy::Native, Vec::new()))
+            }
+            AlterTableOperation::DropColumn { column } => {
+                if !self.column_exists(conn, table, column)? {
+                    return Err(DbError::UnknownColumn {
+                        table: table.to_string(),
+                        column: column.to_string(),
+                    }
+                    .into());
+                }
+
+                // Native DROP COLUMN refuses keys, indexed and constrained
+                // columns; fall back to a rebuild in those cases.
+                let sql = format!(
+                    "ALTER TABLE {} DROP COLUMN {}",
+                    ddl::quote_ident(table),
+                    ddl::quote_ident(column)
+                );
+                debug!(%sql, "Executing SQL");
+                let native = with_savepoint(conn, "drop_column", |conn| {
+                    conn.execute(&sql, []).map_err(anyhow::Error::from)
+                });
+                let (strategy, removed) = match native {
+                    Ok(_) => (AlterStrategy::Native, Vec::new()),
+                    Err(e) => {
+                        debug!("Native DROP COLUMN failed ({}), rebuilding table", e);
+                        let removed = self.rebuild_table(conn, table, |definition, removed| {
+                            drop_column_from_definition(definition, column, removed);
+                            Ok(())
+                        })?;
+                        (AlterStrategy::Rebuild, removed)
+                    }
+                };
+
+                if has_comments {
+                    conn.execute(
+                        "DELETE FROM _table_column_comment WHERE table_name = ? AND column_name = ?",
+                        [table, column],
+                    )?;
+                }
+
+                info!("Dropped column '{}.{}'", table, column);
+                Ok((table.to_string(), strategy, removed))
+            }
+            AlterTableOperation::ChangeType { column, data_type } => {
+                if !self.column_exists(conn, table, column)? {
+                    return Err(DbError::UnknownColumn {
+                        table: table.to_string(),
+                        column: column.to_string(),
+                    }
+                    .into());
+                }
+                let removed = self.rebuild_table(conn, table, |definition, _| {
+                    for col in &mut definition.columns {
+                        if col.name.eq_ignore_ascii_case(column) {
+                            col.data_type = data_type.clone();
+                        }
+                    }
+                    Ok(())
+                })?;
+
+                info!("Changed type of '{}.{}' to {}", table, column, data_type);
+                Ok((table.to_string(), AlterStrategy::Rebuild, removed))
+            }
+            AlterTableOperation::AddConstraint { constraint } => {
+                let removed = self.rebuild_table(conn, table, |definition, _| {
+                    match constraint {
+                        ConstraintDefinition::Unique { columns } => {
+                            definition.unique.push(columns.clone())
+                        }
+                        ConstraintDefinition::Check { expr } => definition.checks.push(expr.clone()),
+                        ConstraintDefinition::NotNull { column } => {
+                            let col = definition
+                                .columns
+                                .iter_mut()
+                                .find(|c| c.name.eq_ignore_ascii_case(column))
+                                .ok_or_else(|| DbError::UnknownColumn {
+                                    table: table.to_string(),
+                                    column: column.to_string(),
+                                })?;
+                            col.not_null = true;
+                        }
+                        ConstraintDefinition::ForeignKey(fk) => {
+                            definition.foreign_keys.push(fk.clone())
+                        }
+                    }
+                    Ok(())
+                })?;
+
+                info!("Added constraint to table '{}'", table);
+                Ok((table.to_string(), AlterStrategy::Rebuild, removed))
             }
-            Err(e) => Err(anyhow!(
-                "Failed to query column comment for '{}.{}': {}",
-                table,
-                column,
-                e
-            )),
         }
     }
 
-    fn table_exists(&self, conn: &rusqlite::Connection, table: &str) -> anyhow::Result<bool> {
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?",
-                [table],
-                |row| row.get(0),
-            )
-            .map_err(|e| anyhow!("Failed to check if table '{}' exists: {}", table, e))?;
+    /// Rebuild `table` following SQLite's generalized ALTER TABLE procedure:
+    /// create the new definition under a temporary name, copy the rows
+    /// across, drop the old table, rename the new one into place and
+    /// recreate its indexes and triggers.
+    ///
+    /// Must run inside a transaction with foreign keys disabled (see
+    /// [`Self::with_foreign_keys_disabled`]). Returns the indexes and
+    /// constraints that `modify` dropped.
+    fn rebuild_table(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+        modify: impl FnOnce(&mut TableDefinition, &mut Vec<String>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Vec<String>> {
+        // The definition would silently drop these
+        let features = ddl::unsupported_table_features(&get_create_sql(conn, table)?);
+        if !features.is_empty() {
+            return Err(DbError::Unsupported(format!(
+                "Cannot rebuild table '{}': it uses {}, which would be lost",
+                table,
+                features.join(", ")
+            ))
+            .into());
+        }
+
+        let old_columns = get_column_names(conn, table)?;
+        let old_rowid = hidden_rowid(conn, table)?;
+        let mut definition = get_table_definition(conn, table)?;
+        let mut removed = Vec::new();
+        modify(&mut definition, &mut removed)?;
+        ddl::validate_table_definition(&definition)?;
+
+        let remaining: Vec<&String> = definition
+            .columns
+            .iter()
+            .map(|c| &c.name)
+            .collect();
+
+        // Indexes and triggers are dropped with the old table. Keep the ones
+        // that still make sense for the new definition.
+        let mut objects = Vec::new();
+        for index in get_indexes(conn, table)? {
+            if index.origin != "c" {
+                continue;
+            }
+            if index
+                .columns
+                .iter()
+                .any(|c| !remaining.iter().any(|r| r.eq_ignore_ascii_case(c)))
+            {
+                removed.push(format!("index {}", index.name));
+                continue;
+            }
+            objects.push(index.name);
+        }
+        let mut stmt = conn.prepare(
+            "SELECT type, name, sql FROM sqlite_master WHERE tbl_name = ? AND type IN ('index', 'trigger') AND sql IS NOT NULL",
+        )?;
+        let saved_sql: Vec<String> = stmt
+            .query_map([table], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|(kind, name, _)| kind == "trigger" || objects.contains(name))
+            .map(|(_, _, sql)| sql)
+            .collect();
+
+        let temp_name = format!("_rebuild_{}", table);
+        if self.table_exists(conn, &temp_name)? {
+            return Err(DbError::TableExists(temp_name.to_string()).into());
+        }
+
+        let mut copied: Vec<String> = old_columns
+            .iter()
+            .filter(|c| remaining.iter().any(|r| r.eq_ignore_ascii_case(c)))
+            .map(|c| ddl::quote_ident(c))
+            .collect();
+        // Rows are keyed on rowid where there is no INTEGER PRIMARY KEY, so
+        // it must survive the copy. A new INTEGER PRIMARY KEY brings its own.
+        if let Some(rowid) = old_rowid.filter(|_| !has_rowid_alias(&definition)) {
+            if remaining.iter().any(|r| r.eq_ignore_ascii_case(rowid)) {
+                return Err(DbError::Unsupported(format!(
+                    "Cannot rebuild table '{}': a column would hide its {}",
+                    table, rowid
+                ))
+                .into());
+            }
+            copied.insert(0, rowid.to_string());
+        }
+
+        let statements = [
+            ddl::create_table_sql(&definition, &temp_name),
+            format!(
+                "INSERT INTO {} ({cols}) SELECT {cols} FROM {}",
+                ddl::quote_ident(&temp_name),
+                ddl::quote_ident(table),
+                cols = copied.join(", ")
+            ),
+            format!("DROP TABLE {}", ddl::quote_ident(table)),
+            // Keep references to the old name in other tables, views and
+            // triggers untouched while the new table takes its place.
+            "PRAGMA legacy_alter_table = ON".to_string(),
+            format!(
+                "ALTER TABLE {} RENAME TO {}",
+                ddl::quote_ident(&temp_name),
+                ddl::quote_ident(table)
+            ),
+            "PRAGMA legacy_alter_table = OFF".to_string(),
+        ];
+
+        for (i, stmt) in statements.iter().chain(saved_sql.iter()).enumerate() {
+            debug!(sql = %stmt, "Executing rebuild statement {}", i);
+            conn.execute_batch(stmt)
+                .with_context(|| format!("Failed to rebuild table '{}'", table))?;
+        }
 
-        Ok(count > 0)
+        info!("Rebuilt table '{}'", table);
+        Ok(removed)
     }
 
-    fn column_exists(
+    fn apply_migration_source(
         &self,
         conn: &rusqlite::Connection,
-        table: &str,
-        column: &str,
-    ) -> anyhow::Result<bool> {
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name=?",
-                [table, column],
-                |row| row.get(0),
-            )
-            .map_err(|e| {
-                anyhow!(
-                    "Failed to check if column '{}'.'{}' exists: {}",
-                    table,
-                    column,
-                    e
-                )
-            })?;
+        source: &MigrationSource,
+    ) -> anyhow::Result<()> {
+        let steps = match source {
+            MigrationSource::Sql(sql) => {
+                debug!(%sql, "Executing SQL");
+                return conn.execute_batch(sql).map_err(anyhow::Error::from);
+            }
+            MigrationSource::Steps(steps) => steps,
+        };
 
-        Ok(count > 0)
+        for step in steps {
+            match step {
+                MigrationStep::CreateTable(definition) => {
+                    self.create_table_internal(conn, definition)?
+                }
+                MigrationStep::AddColumn { table, column } => {
+                    self.add_column_internal(conn, table, column)?
+                }
+                MigrationStep::CreateIndex(index) => self.create_index_internal(conn, index)?,
+                MigrationStep::DropIndex { name, if_exists } => {
+                    self.drop_index_internal(conn, name, *if_exists)?;
+                }
+                MigrationStep::AlterTable { table, operation } => {
+                    self.alter_table_internal(conn, table, operation)?;
+                }
+                MigrationStep::Sql { sql } => {
+                    debug!(%sql, "Executing SQL");
+                    conn.execute_batch(sql)?
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn set_table_comment_internal(
+    fn migrate_internal(
         &self,
         conn: &rusqlite::Connection,
-        table: &str,
-        desc: &str,
-    ) -> anyhow::Result<()> {
-        if !self.table_exists(conn, table)? {
-            return Err(anyhow!("Table '{}' does not exist", table));
-        }
+        dir: &Path,
+        command: MigrationCommand,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> anyhow::Result<MigrationReport> {
+        let files = migrations::discover(dir)?;
+        let applied = migrations::applied(conn)?;
+        let mut executed = Vec::new();
+
+        match command {
+            MigrationCommand::Status => {}
+            MigrationCommand::Up => {
+                if let Some(modified) = migrations::status(&files, &applied)?
+                    .into_iter()
+                    .find(|m| m.state == MigrationState::Modified)
+                {
+                    return Err(anyhow!(
+                        "Migration {} ({}) has been modified since it was applied",
+                        modified.version,
+                        modified.name
+                    ));
+                }
 
-        self.ensure_auxiliary_tables_exist(conn)?;
+                let pending = files.iter().filter(|f| {
+                    !applied.iter().any(|a| a.version == f.version)
+                        && target.is_none_or(|t| f.version <= t)
+                });
+
+                for file in pending {
+                    let path = file.up.as_ref().ok_or_else(|| {
+                        anyhow!("Migration {} ({}) has no up file", file.version, file.name)
+                    })?;
+                    let (source, checksum) = migrations::load(path)?;
+
+                    if !dry_run {
+                        self.with_foreign_keys_disabled(conn, |conn| {
+                            migrations::ensure_migrations_table(conn)?;
+                            self.apply_migration_source(conn, &source)?;
+                            conn.execute(
+                                "INSERT INTO _migrations (version, name, checksum) VALUES (?, ?, ?)",
+                                rusqlite::params![file.version, file.name, checksum],
+                            )?;
+                            Ok(())
+                        })
+                        .with_context(|| {
+                            format!("Migration {} ({}) failed", file.version, file.name)
+                        })?;
+                        info!("Applied migration {} ({})", file.version, file.name);
+                    }
 
-        conn.execute(
-            "INSERT OR REPLACE INTO _table_comment (table_name, table_desc) VALUES (?, ?)",
-            [table, desc],
-        )
-        .map_err(|e| anyhow!("Failed to set table comment for '{}': {}", table, e))?;
+                    executed.push(MigrationRun {
+                        version: file.version,
+                        name: file.name.clone(),
+                        direction: MigrationCommand::Up,
+                        statements: source.describe(),
+                    });
+                }
+            }
+            MigrationCommand::Down => {
+                let to_revert: Vec<&AppliedMigration> = match target {
+                    Some(t) => applied.iter().rev().filter(|a| a.version > t).collect(),
+                    None => applied.last().into_iter().collect(),
+                };
 
-        info!("Set comment for table '{}': {}", table, desc);
-        Ok(())
+                for record in to_revert {
+                    let path = files
+                        .iter()
+                        .find(|f| f.version == record.version)
+                        .and_then(|f| f.down.as_ref())
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "Migration {} ({}) has no down file",
+                                record.version,
+                                record.name
+                            )
+                        })?;
+                    let (source, _) = migrations::load(path)?;
+
+                    if !dry_run {
+                        self.with_foreign_keys_disabled(conn, |conn| {
+                            self.apply_migration_source(conn, &source)?;
+                            conn.execute(
+                                "DELETE FROM _migrations WHERE version = ?",
+                                [record.version],
+                            )?;
+                            Ok(())
+                        })
+                        .map_err(|e| {
+                            anyhow!(
+                                "Reverting migration {} ({}) failed: {}",
+                                record.version,
+                                record.name,
+                                e
+                            )
+                        })?;
+                        info!("Reverted migration {} ({})", record.version, record.name);
+                    }
+
+                    executed.push(MigrationRun {
+                        version: record.version,
+                        name: record.name.clone(),
+                        direction: MigrationCommand::Down,
+                        statements: source.describe(),
+                    });
+                }
+            }
+        }
+
+        let applied = migrations::applied(conn)?;
+        Ok(MigrationReport {
+            command,
+            dry_run,
+            current_version: applied.last().map(|a| a.version),
+            executed,
+            migrations: migrations::status(&files, &applied)?,
+        })
     }
+}
 
-    fn set_column_comment_internal(
-        &self,
-        conn: &rusqlite::Connection,
-        table: &str,
-        column: &str,
-        desc: &str,
-    ) -> anyhow::Result<()> {
-        if !self.table_exists(conn, table)? {
-            return Err(anyhow!("Table '{}' does not exist", table));
+/// Collect the tables written by each transaction with the update hook, and
+/// publish them once the commit hook fires. Rolled back writes are dropped.
+fn register_change_hooks(
+    conn: &rusqlite::Connection,
+    changes: broadcast::Sender<DataChange>,
+) -> anyhow::Result<()> {
+    let pending: Arc<Mutex<BTreeSet<String>>> = Arc::default();
+
+    let written = pending.clone();
+    conn.update_hook(Some(
+        move |_action: rusqlite::hooks::Action, _db: &str, table: &str, _rowid: i64| {
+            if let Ok(mut written) = written.lock() {
+                written.insert(table.to_string());
+            }
+        },
+    ))?;
+
+    let committed = pending.clone();
+    conn.commit_hook(Some(move || {
+        if let Ok(mut committed) = committed.lock() {
+            let tables = std::mem::take(&mut *committed);
+            if !tables.is_empty() {
+                // No receivers is fine: nobody is subscribed
+                let _ = changes.send(DataChange::Tables(tables.into_iter().collect()));
+            }
         }
+        // Returning true would turn the commit into a rollback
+        false
+    }))?;
 
-        if !self.column_exists(conn, table, column)? {
-            return Err(anyhow!("Column '{}'.'{}' does not exist", table, column));
+    conn.rollback_hook(Some(move || {
+        if let Ok(mut rolled_back) = pending.lock() {
+            rolled_back.clear();
         }
+    }))?;
 
-        self.ensure_auxiliary_tables_exist(conn)?;
+    Ok(())
+}
 
-        conn.execute(
-            "INSERT OR REPLACE INTO _table_column_comment (table_name, column_name, column_desc) VALUES (?, ?, ?)",
-            [table, column, desc],
-        ).map_err(|e| anyhow!("Failed to set column comment for '{}.'{}': {}", table, column, e))?;
+/// `PRAGMA data_version` changes whenever another connection commits, which
+/// the hooks above cannot see. Stops once the database is dropped.
+async fn poll_data_version(
+    conn: Weak<Mutex<rusqlite::Connection>>,
+    changes: broadcast::Sender<DataChange>,
+) {
+    let mut interval = tokio::time::interval(DATA_VERSION_POLL_INTERVAL);
+    let mut last_version: Option<i64> = None;
+
+    loop {
+        interval.tick().await;
+        let Some(conn) = conn.upgrade() else {
+            break;
+        };
 
-        info!("Set comment for column '{}.'{}': {}", table, column, desc);
-        Ok(())
+        let version = tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+            anyhow::Ok(conn.query_row("PRAGMA data_version", [], |row| row.get::<_, i64>(0))?)
+        })
+        .await;
+        match version {
+            Ok(Ok(version)) => {
+                if last_version.is_some_and(|last| last != version) {
+                    debug!("Database changed by another connection");
+                    let _ = changes.send(DataChange::External);
+                }
+                last_version = Some(version);
+            }
+            Ok(Err(e)) => tracing::warn!("Failed to read data_version: {:#}", e),
+            Err(_) => break,
+        }
+    }
+}
+
+/// Run `f` inside a savepoint, rolling back to it on error. Unlike a
+/// transaction, savepoints nest, so this is safe whether or not the caller
+/// already holds a transaction.
+fn with_savepoint<T>(
+    conn: &rusqlite::Connection,
+    name: &str,
+    f: impl FnOnce(&rusqlite::Connection) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    conn.execute_batch(&format!("SAVEPOINT {}", name))?;
+    match f(conn) {
+        Ok(value) => {
+            conn.execute_batch(&format!("RELEASE {}", name))?;
+            Ok(value)
+        }
+        Err(e) => {
+            conn.execute_batch(&format!("ROLLBACK TO {name}; RELEASE {name}"))?;
+            Err(e)
+        }
     }
 }
 
@@ -340,7 +1250,7 @@ fn sqlite_row_to_json(row: &rusqlite::Row) -> anyhow::Result<serde_json::Value>
             serde_json::Value::Number(int.into())
         } else if let Ok(Some(float)) = row.get::<_, Option<f64>>(i) {
             serde_json::Number::from_f64(float)
-                .map(|n| serde_json::Value::Number(n))
+                .map(serde_json::Value::Number)
                 .unwrap_or(serde_json::Value::Null)
         } else if let Ok(Some(bool)) = row.get::<_, Option<bool>>(i) {
             serde_json::Value::Bool(bool)
@@ -354,13 +1264,28 @@ fn sqlite_row_to_json(row: &rusqlite::Row) -> anyhow::Result<serde_json::Value>
     Ok(serde_json::Value::Object(map))
 }
 
+/// Filter keys become SQL, so each must name a column of `table` (or its
+/// rowid) and is quoted before use.
 fn build_where_clause(
+    conn: &rusqlite::Connection,
+    table: &str,
     filter: &QueryFilter,
 ) -> anyhow::Result<(String, Vec<rusqlite::types::Value>)> {
     let mut conditions = Vec::new();
     let mut params = Vec::new();
-
-    for (column, filter_value) in &filter.conditions {
+    let columns = get_column_names(conn, table)?;
+
+    for (key, filter_value) in &filter.conditions {
+        let known = key.eq_ignore_ascii_case("rowid")
+            || columns.iter().any(|c| c.eq_ignore_ascii_case(key));
+        if !known {
+            return Err(DbError::UnknownColumn {
+                table: table.to_string(),
+                column: key.clone(),
+            }
+            .into());
+        }
+        let column = &ddl::quote_ident(key);
         match filter_value {
             FilterValue::Direct(val) => {
                 conditions.push(format!("{} = ?", column));
@@ -443,26 +1368,30 @@ fn validate_order_clauses(
         if order.random == Some(true) {
             random_count += 1;
             if order.column.is_some() || order.direction.is_some() {
-                return Err(anyhow!(
+                return Err(DbError::InvalidArgument(
                     "Invalid order clause: 'random' cannot be combined with 'column' or 'direction'"
-                ));
+                        .to_string(),
+                )
+                .into());
             }
         } else if let Some(col) = &order.column {
             if !valid_columns.contains(col) {
-                return Err(anyhow!(
+                return Err(DbError::InvalidArgument(format!(
                     "Invalid column '{}' in order clause. Valid columns: {}",
                     col,
                     valid_columns.join(", ")
-                ));
+                ))
+                .into());
             }
         }
     }
 
     if random_count > 1 {
-        return Err(anyhow!(
+        return Err(DbError::InvalidArgument(format!(
             "Only one random order clause is allowed, found {}",
             random_count
-        ));
+        ))
+        .into());
     }
 
     Ok(())
@@ -479,15 +1408,15 @@ fn build_order_clause(orders: &[OrderClause]) -> String {
                     Some(OrderDirection::Asc) => "ASC",
                     Some(OrderDirection::Desc) | None => "DESC",
                 };
-                format!("{} {}", o.column.as_ref().unwrap(), dir)
+                format!("{} {}", ddl::quote_ident(o.column.as_ref().unwrap()), dir)
             }
         })
         .collect();
     format!("ORDER BY {}", clauses.join(", "))
 }
 
-fn get_column_names(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<Vec<String>> {
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+pub(crate) fn get_column_names(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", ddl::quote_ident(table)))?;
     let mut rows = stmt.query([])?;
     let mut columns = Vec::new();
 
@@ -499,93 +1428,606 @@ fn get_column_names(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<
     Ok(columns)
 }
 
-#[async_trait::async_trait]
-impl DatabaseAdapter for SqliteDatabase {
-    async fn list_tables(&self) -> anyhow::Result<Vec<String>> {
-        debug!("Listing tables in database");
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+/// The single-column primary key of `table`, or `rowid` when it has none or
+/// a composite one.
+fn primary_key_column(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<String> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", ddl::quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut primary_keys = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let is_pk: i32 = row.get(5)?;
+        if is_pk > 0 {
+            let name: String = row.get(1)?;
+            primary_keys.push(name);
+        }
+    }
+
+    if primary_keys.len() == 1 {
+        Ok(primary_keys.remove(0))
+    } else {
+        Ok("rowid".to_string())
+    }
+}
+
+fn get_foreign_keys(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<Vec<ForeignKeyInfo>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA foreign_key_list({})", ddl::quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut foreign_keys: Vec<(i64, ForeignKeyInfo)> = Vec::new();
+
+    // Columns of a composite foreign key share an id and arrive as consecutive rows
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let ref_table: String = row.get(2)?;
+        let from: String = row.get(3)?;
+        let to: Option<String> = row.get(4)?;
+
+        match foreign_keys.last_mut() {
+            Some((last_id, fk)) if *last_id == id => {
+                fk.columns.push(from);
+                fk.ref_columns.extend(to);
+            }
+            _ => foreign_keys.push((
+                id,
+                ForeignKeyInfo {
+                    columns: vec![from],
+                    ref_table,
+                    ref_columns: to.into_iter().collect(),
+                    on_update: row.get(5)?,
+                    on_delete: row.get(6)?,
+                },
+            )),
+        }
+    }
+
+    Ok(foreign_keys.into_iter().map(|(_, fk)| fk).collect())
+}
+
+fn get_indexes(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<Vec<IndexInfo>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA index_list({})", ddl::quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut indexes = Vec::new();
 
-        let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+    while let Some(row) = rows.next()? {
+        indexes.push(IndexInfo {
+            name: row.get(1)?,
+            columns: Vec::new(),
+            unique: row.get::<_, i32>(2)? == 1,
+            origin: row.get(3)?,
+        });
+    }
 
+    for index in &mut indexes {
+        let mut stmt = conn.prepare(&format!("PRAGMA index_info({})", ddl::quote_ident(&index.name)))?;
         let mut rows = stmt.query([])?;
-        let mut tables = Vec::new();
         while let Some(row) = rows.next()? {
-            tables.push(row.get::<_, String>(0)?);
+            // Expression index columns have no name
+            let name: Option<String> = row.get(2)?;
+            index.columns.push(name.unwrap_or_else(|| "<expr>".to_string()));
         }
+    }
 
-        debug!("Found {} tables", tables.len());
-        Ok(tables)
+    indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(indexes)
+}
+
+fn get_table_comment(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT table_desc FROM _table_comment WHERE table_name = ?",
+        [table],
+        |row| row.get::<_, String>(0),
+    );
+
+    match result {
+        Ok(desc) => {
+            debug!("Retrieved table comment for '{}': {}", table, desc);
+            Ok(Some(desc))
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            debug!("No table comment found for '{}'", table);
+            Ok(None)
+        }
+        Err(e) => Err(anyhow!(
+            "Failed to query table comment for '{}': {}",
+            table,
+            e
+        )),
     }
+}
 
-    async fn get_schema(&self, table: &str) -> anyhow::Result<TableSchema> {
-        debug!("Getting schema for table '{}'", table);
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+fn get_column_comment(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+) -> anyhow::Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT column_desc FROM _table_column_comment WHERE table_name = ? AND column_name = ?",
+        [table, column],
+        |row| row.get::<_, String>(0),
+    );
+
+    match result {
+        Ok(desc) => {
+            debug!(
+                "Retrieved column comment for '{}.{}': {}",
+                table, column, desc
+            );
+            Ok(Some(desc))
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            debug!("No column comment found for '{}.{}'", table, column);
+            Ok(None)
+        }
+        Err(e) => Err(anyhow!(
+            "Failed to query column comment for '{}.{}': {}",
+            table,
+            column,
+            e
+        )),
+    }
+}
 
-        self.ensure_auxiliary_tables_exist(&conn)?;
+/// User tables in `conn`, ordered by name, without SQLite's and the
+/// server's internal tables.
+pub(crate) fn user_tables(conn: &rusqlite::Connection) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tables
+        .into_iter()
+        .filter(|t| !is_internal_table(t))
+        .collect())
+}
 
-        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+pub(crate) fn auxiliary_tables_exist(conn: &rusqlite::Connection) -> anyhow::Result<bool> {
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name IN ('_table_comment', '_table_column_comment')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| anyhow!("Failed to check for auxiliary tables: {}", e))?;
 
-        let mut rows = stmt.query([])?;
-        let mut columns = Vec::new();
-        let mut primary_keys = Vec::new();
+    Ok(count == 2)
+}
 
-        while let Some(row) = rows.next()? {
-            let is_pk: i32 = row.get(5)?;
-            if is_pk > 0 {
-                let name: String = row.get(1)?;
-                primary_keys.push(name.clone());
+/// Structured metadata of the columns of `table`, keyed by column name.
+/// Empty if the auxiliary tables are missing or predate the metadata columns.
+fn get_column_metadata(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<HashMap<String, ColumnMetadata>> {
+    let mut metadata = HashMap::new();
+    if !auxiliary_tables_exist(conn)?
+        || !get_column_names(conn, "_table_column_comment")?.contains(&"unit".to_string())
+    {
+        return Ok(metadata);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT column_name, unit, allowed_values, examples, format, sensitivity, deprecated
+         FROM _table_column_comment WHERE table_name = ?",
+    )?;
+    let mut rows = stmt.query([table])?;
+
+    let parse_list = |text: Option<String>| {
+        text.and_then(|t| serde_json::from_str::<Vec<serde_json::Value>>(&t).ok())
+    };
+    let parse_name = |text: Option<String>| text.map(serde_json::Value::String);
+
+    while let Some(row) = rows.next()? {
+        let column: String = row.get(0)?;
+        metadata.insert(
+            column,
+            ColumnMetadata {
+                unit: row.get(1)?,
+                allowed_values: parse_list(row.get(2)?),
+                examples: parse_list(row.get(3)?),
+                format: parse_name(row.get(4)?).and_then(|v| serde_json::from_value(v).ok()),
+                sensitivity: parse_name(row.get(5)?).and_then(|v| serde_json::from_value(v).ok()),
+                deprecated: row.get::<_, Option<bool>>(6)?.unwrap_or(false),
+            },
+        );
+    }
+
+    Ok(metadata)
+}
+
+/// Reject values outside a column's `allowed_values`.
+fn check_allowed_values(
+    metadata: &HashMap<String, ColumnMetadata>,
+    table: &str,
+    data: &serde_json::Map<String, serde_json::Value>,
+) -> anyhow::Result<()> {
+    for (column, value) in data {
+        // SQLite matches column names without regard to ASCII case
+        let allowed = metadata
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(column))
+            .and_then(|(_, m)| m.allowed_values.as_ref());
+        let Some(allowed) = allowed else {
+            continue;
+        };
+        if !value.is_null() && !allowed.contains(value) {
+            return Err(DbError::ConstraintViolation(format!(
+                "Value {} is not allowed for column '{}'.'{}'; allowed values: {}",
+                value,
+                table,
+                column,
+                serde_json::Value::Array(allowed.clone())
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Names of the PII and secret columns in `metadata`, lowercased.
+fn masked_columns(metadata: &HashMap<String, ColumnMetadata>) -> Vec<String> {
+    metadata
+        .iter()
+        .filter(|(_, m)| m.sensitivity.is_some_and(|s| s.is_masked()))
+        .map(|(column, _)| column.to_ascii_lowercase())
+        .collect()
+}
+
+/// Reject filters and orderings on PII and secret columns, which would
+/// reveal masked values one comparison at a time.
+fn check_masked_columns(
+    conn: &rusqlite::Connection,
+    table: &str,
+    filters: Option<&QueryFilter>,
+    orders: Option<&[OrderClause]>,
+) -> anyhow::Result<()> {
+    let masked = masked_columns(&get_column_metadata(conn, table)?);
+    if masked.is_empty() {
+        return Ok(());
+    }
+
+    let ordered = orders.into_iter().flatten().filter_map(|o| o.column.as_ref());
+    let referenced = filters.into_iter().flat_map(|f| f.conditions.keys()).chain(ordered);
+    for column in referenced {
+        if masked.contains(&column.to_ascii_lowercase()) {
+            return Err(DbError::InvalidArgument(format!(
+                "Column '{}'.'{}' is masked and cannot be used in filters or ordering; \
+                 start the server with --admin to do so",
+                table, column
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Replace the values of PII and secret columns in query results.
+fn mask_sensitive_values(
+    metadata: &HashMap<String, ColumnMetadata>,
+    rows: &mut [serde_json::Value],
+) {
+    let masked = masked_columns(metadata);
+    if masked.is_empty() {
+        return;
+    }
+
+    for row in rows.iter_mut().filter_map(|r| r.as_object_mut()) {
+        for (column, value) in row.iter_mut() {
+            if !value.is_null() && masked.contains(&column.to_ascii_lowercase()) {
+                *value = serde_json::Value::String(MASKED_VALUE.to_string());
             }
+        }
+    }
+}
 
-            columns.push(ColumnInfo {
-                name: row.get::<_, String>(1)?,
-                desc: None,
-                data_type: row
-                    .get::<_, Option<String>>(2)?
-                    .unwrap_or_else(|| "ANY".to_string()),
-                not_null: row.get::<_, i32>(3)? == 1,
-                default_value: row.get::<_, Option<String>>(4)?,
-                is_primary_key: is_pk > 0,
-            });
+/// Read the schema of `table` without modifying the database. Comments are
+/// `None` where none have been stored, and `primary_key` is `None` for a
+/// composite key.
+pub(crate) fn introspect_table(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<TableSchema> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", ddl::quote_ident(table)))?;
+
+    let mut rows = stmt.query([])?;
+    let mut columns = Vec::new();
+    let mut primary_keys = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let is_pk: i32 = row.get(5)?;
+        if is_pk > 0 {
+            let name: String = row.get(1)?;
+            primary_keys.push(name.clone());
         }
 
-        let primary_key = if primary_keys.len() == 1 {
-            primary_keys[0].clone()
-        } else if primary_keys.is_empty() {
-            "rowid".to_string()
-        } else {
-            return Err(anyhow!("Composite primary keys not supported"));
-        };
+        columns.push(ColumnInfo {
+            name: row.get::<_, String>(1)?,
+            desc: None,
+            data_type: row
+                .get::<_, Option<String>>(2)?
+                .unwrap_or_else(|| "ANY".to_string()),
+            not_null: row.get::<_, i32>(3)? == 1,
+            default_value: row.get::<_, Option<String>>(4)?,
+            is_primary_key: is_pk > 0,
+            metadata: ColumnMetadata::default(),
+        });
+    }
 
-        self.initialize_default_table_comment(&conn, table)?;
-        self.initialize_default_column_comments(&conn, table, &columns)?;
-
-        let table_desc = self
-            .get_table_comment(&conn, table)?
-            .unwrap_or_else(|| table.to_string());
-
-        let mut columns_with_desc = Vec::new();
-        for column in columns {
-            let column_desc = self
-                .get_column_comment(&conn, table, &column.name)?
-                .unwrap_or_else(|| column.name.clone());
-            columns_with_desc.push(ColumnInfo {
-                desc: Some(column_desc),
-                ..column
-            });
+    let primary_key = match primary_keys.len() {
+        0 => Some("rowid".to_string()),
+        1 => Some(primary_keys[0].clone()),
+        _ => None,
+    };
+
+    let mut desc = None;
+    if auxiliary_tables_exist(conn)? {
+        desc = get_table_comment(conn, table)?;
+        let mut metadata = get_column_metadata(conn, table)?;
+        for column in &mut columns {
+            column.desc = get_column_comment(conn, table, &column.name)?;
+            column.metadata = metadata.remove(&column.name).unwrap_or_default();
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT name, sql FROM sqlite_master WHERE type='trigger' AND tbl_name=? ORDER BY name",
+    )?;
+    let triggers = stmt
+        .query_map([table], |row| {
+            Ok(TriggerInfo {
+                name: row.get(0)?,
+                sql: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(TableSchema {
+        name: table.to_string(),
+        desc,
+        columns,
+        primary_key,
+        foreign_keys: get_foreign_keys(conn, table)?,
+        indexes: get_indexes(conn, table)?,
+        triggers,
+        checks: ddl::extract_check_constraints(&get_create_sql(conn, table)?),
+    })
+}
+
+pub(crate) fn get_create_sql(conn: &rusqlite::Connection, table: &str) -> anyhow::Result<String> {
+    conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type='table' AND name=?",
+        [table],
+        |row| row.get(0),
+    )
+    .map_err(|e| anyhow!("Failed to read definition of table '{}': {}", table, e))
+}
+
+/// The name to select `table`'s rowid by when it has to be copied
+/// explicitly. `None` for WITHOUT ROWID tables and for tables whose rowid is
+/// an INTEGER PRIMARY KEY column, which is copied like any other column.
+pub(crate) fn hidden_rowid(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<Option<&'static str>> {
+    if ddl::unsupported_table_features(&get_create_sql(conn, table)?).contains(&"WITHOUT ROWID") {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(&format!("PRAGMA table_xinfo({})", ddl::quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut columns = Vec::new();
+    let mut primary_key_types = Vec::new();
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if row.get::<_, i32>(5)? > 0 {
+            primary_key_types.push(row.get::<_, Option<String>>(2)?.unwrap_or_default());
+        }
+        columns.push(name);
+    }
+    if primary_key_types.len() == 1 && primary_key_types[0].eq_ignore_ascii_case("INTEGER") {
+        return Ok(None);
+    }
+
+    // Columns with these names hide the rowid behind them
+    ["rowid", "_rowid_", "oid"]
+        .into_iter()
+        .find(|alias| !columns.iter().any(|c| c.eq_ignore_ascii_case(alias)))
+        .map(Some)
+        .ok_or_else(|| {
+            DbError::Unsupported(format!(
+                "Table '{}' has columns hiding every name of its rowid",
+                table
+            ))
+            .into()
+        })
+}
+
+/// Whether the table `definition` creates keeps its rowid in an INTEGER
+/// PRIMARY KEY column.
+fn has_rowid_alias(definition: &TableDefinition) -> bool {
+    let mut keys = definition.columns.iter().filter(|c| c.primary_key);
+    match (keys.next(), keys.next()) {
+        (Some(key), None) => key.data_type.eq_ignore_ascii_case("INTEGER"),
+        _ => false,
+    }
+}
+
+/// Reconstruct a [`TableDefinition`] for an existing table from its pragmas,
+/// with CHECK constraints recovered from the original `CREATE TABLE` text.
+pub(crate) fn get_table_definition(
+    conn: &rusqlite::Connection,
+    table: &str,
+) -> anyhow::Result<TableDefinition> {
+    let create_sql = get_create_sql(conn, table)?;
+
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", ddl::quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut columns = Vec::new();
+    let mut pk_count = 0;
+
+    while let Some(row) = rows.next()? {
+        let is_pk = row.get::<_, i32>(5)? > 0;
+        if is_pk {
+            pk_count += 1;
+        }
+        columns.push(ColumnDefinition {
+            name: row.get(1)?,
+            data_type: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            desc: None,
+            not_null: row.get::<_, i32>(3)? == 1,
+            primary_key: is_pk,
+            autoincrement: false,
+            unique: false,
+            default: None,
+            default_expr: row.get(4)?,
+            check: None,
+        });
+    }
+
+    if pk_count > 1 {
+        return Err(DbError::Unsupported(format!(
+            "Cannot rebuild table '{}': composite primary keys not supported",
+            table
+        ))
+        .into());
+    }
+    if ddl::has_autoincrement(&create_sql) {
+        for column in columns.iter_mut().filter(|c| c.primary_key) {
+            column.autoincrement = true;
+        }
+    }
+
+    let unique = get_indexes(conn, table)?
+        .into_iter()
+        .filter(|index| index.origin == "u")
+        .map(|index| index.columns)
+        .collect();
+
+    let foreign_keys = get_foreign_keys(conn, table)?
+        .into_iter()
+        .map(|fk| ForeignKeyDefinition {
+            columns: fk.columns,
+            ref_table: fk.ref_table,
+            ref_columns: fk.ref_columns,
+            on_delete: ForeignKeyAction::from_sql(&fk.on_delete),
+            on_update: ForeignKeyAction::from_sql(&fk.on_update),
+        })
+        .collect();
+
+    Ok(TableDefinition {
+        name: table.to_string(),
+        desc: None,
+        columns,
+        foreign_keys,
+        unique,
+        checks: ddl::extract_check_constraints(&create_sql),
+        if_not_exists: false,
+    })
+}
+
+fn drop_column_from_definition(
+    definition: &mut TableDefinition,
+    column: &str,
+    removed: &mut Vec<String>,
+) {
+    definition
+        .columns
+        .retain(|c| !c.name.eq_ignore_ascii_case(column));
+
+    definition.unique.retain(|columns| {
+        let keep = !columns.iter().any(|c| c.eq_ignore_ascii_case(column));
+        if !keep {
+            removed.push(format!("unique ({})", columns.join(", ")));
+        }
+        keep
+    });
+    definition.checks.retain(|expr| {
+        let keep = !ddl::references_column(expr, column);
+        if !keep {
+            removed.push(format!("check ({})", expr));
+        }
+        keep
+    });
+    definition.foreign_keys.retain(|fk| {
+        let keep = !fk.columns.iter().any(|c| c.eq_ignore_ascii_case(column));
+        if !keep {
+            removed.push(format!(
+                "foreign key ({}) references {}",
+                fk.columns.join(", "),
+                fk.ref_table
+            ));
         }
+        keep
+    });
+}
+
+fn check_foreign_keys(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let violations: Vec<(String, Option<i64>, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some((table, rowid, parent)) = violations.first() {
+        return Err(DbError::ConstraintViolation(format!(
+            "Foreign key check failed with {} violation(s), e.g. row {} of '{}' references missing row in '{}'",
+            violations.len(),
+            rowid.map(|r| r.to_string()).unwrap_or_else(|| "?".to_string()),
+            table,
+            parent
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl DatabaseAdapter for SqliteDatabase {
+    async fn list_tables(&self, include_internal: bool) -> anyhow::Result<Vec<String>> {
+        debug!("Listing tables in database");
+        self.run(move |_, conn| {
+            let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+
+            let mut rows = stmt.query([])?;
+            let mut tables = Vec::new();
+            while let Some(row) = rows.next()? {
+                let table: String = row.get(0)?;
+                if include_internal || !is_internal_table(&table) {
+                    tables.push(table);
+                }
+            }
+
+            debug!("Found {} tables", tables.len());
+            Ok(tables)
+        })
+        .await
+    }
+
+    async fn get_schema(&self, table: &str) -> anyhow::Result<TableSchema> {
+        debug!("Getting schema for table '{}'", table);
+        let table = table.to_string();
+        self.run(move |db, conn| {
+            db.get_schema_internal(conn, &table)
+        })
+        .await
+    }
 
-        Ok(TableSchema {
-            name: table.to_string(),
-            desc: Some(table_desc),
-            columns: columns_with_desc,
-            primary_key: Some(primary_key),
+    async fn list_schemas(&self) -> anyhow::Result<Vec<TableSchema>> {
+        debug!("Reading schema of all tables");
+        self.run(move |_, conn| {
+            user_tables(conn)?
+                .iter()
+                .map(|table| introspect_table(conn, table))
+                .collect()
         })
+        .await
     }
 
     async fn select(
@@ -596,54 +2038,60 @@ impl DatabaseAdapter for SqliteDatabase {
         limit: Option<usize>,
         offset: Option<usize>,
     ) -> anyhow::Result<Vec<serde_json::Value>> {
-        debug!("Querying table '{}' with filters: {:?}, orders: {:?}", table, filters, orders);
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-
-        if let Some(ref order_clauses) = orders {
-            let valid_columns = get_column_names(&conn, table)?;
-            validate_order_clauses(order_clauses, &valid_columns)?;
-        }
+        self.check_table_access(table)?;
+
+        debug!(?filters, ?orders, "Querying table '{}'", table);
+        let table = table.to_string();
+        let admin = self.admin;
+        self.run(move |_, conn| {
+            let table = &table;
+            if !admin {
+                check_masked_columns(conn, table, filters.as_ref(), orders.as_deref())?;
+            }
+            if let Some(ref order_clauses) = orders {
+                let valid_columns = get_column_names(conn, table)?;
+                validate_order_clauses(order_clauses, &valid_columns)?;
+            }
 
-        let mut sql = format!("SELECT * FROM {}", table);
-        let mut params = Vec::new();
+            let mut sql = format!("SELECT * FROM {}", ddl::quote_ident(table));
+            let mut params = Vec::new();
 
-        if let Some(filter) = filters {
-            let (where_clause, where_params) = build_where_clause(&filter)?;
-            sql.push_str(&format!(" WHERE {}", where_clause));
-            params = where_params;
-        }
+            if let Some(filter) = filters {
+                let (where_clause, where_params) = build_where_clause(conn, table, &filter)?;
+                sql.push_str(&format!(" WHERE {}", where_clause));
+                params = where_params;
+            }
 
-        if let Some(ref order_clauses) = orders {
-            if !order_clauses.is_empty() {
-                sql.push_str(&format!(" {}", build_order_clause(order_clauses)));
+            if let Some(ref order_clauses) = orders {
+                if !order_clauses.is_empty() {
+                    sql.push_str(&format!(" {}", build_order_clause(order_clauses)));
+                }
             }
-        }
 
-        if let Some(limit) = limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
-        }
+            if let Some(limit) = limit {
+                sql.push_str(&format!(" LIMIT {}", limit));
+            }
+            if let Some(offset) = offset {
+                sql.push_str(&format!(" OFFSET {}", offset));
+            }
 
-        debug!("Executing SQL: {}", sql);
-        debug!("Params: {:?}", params);
+            debug!(%sql, ?params, "Executing SQL");
 
-        let mut stmt = conn.prepare(&sql)?;
-        let param_refs: Vec<&dyn rusqlite::ToSql> =
-            params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+            let mut stmt = conn.prepare(&sql)?;
+            let param_refs: Vec<&dyn rusqlite::ToSql> =
+                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
 
-        let mut rows = stmt.query(param_refs.as_slice())?;
-        let mut results = Vec::new();
-        while let Some(row) = rows.next()? {
-            results.push(sqlite_row_to_json(row)?);
-        }
+            let mut rows = stmt.query(param_refs.as_slice())?;
+            let mut results = Vec::new();
+            while let Some(row) = rows.next()? {
+                results.push(sqlite_row_to_json(row)?);
+            }
+            mask_sensitive_values(&get_column_metadata(conn, table)?, &mut results);
 
-        debug!("Query returned {} rows", results.len());
-        Ok(results)
+            debug!("Query returned {} rows", results.len());
+            Ok(results)
+        })
+        .await
     }
 
     async fn count(
@@ -651,76 +2099,81 @@ impl DatabaseAdapter for SqliteDatabase {
         table: &str,
         filters: Option<QueryFilter>,
     ) -> anyhow::Result<usize> {
-        debug!("Counting records in table '{}' with filters: {:?}", table, filters);
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-
-        let mut sql = format!("SELECT COUNT(*) FROM {}", table);
-        let mut params = Vec::new();
+        self.check_table_access(table)?;
+
+        debug!(?filters, "Counting records in table '{}'", table);
+        let table = table.to_string();
+        let admin = self.admin;
+        self.run(move |_, conn| {
+            let table = &table;
+            if !admin {
+                check_masked_columns(conn, table, filters.as_ref(), None)?;
+            }
+            let mut sql = format!("SELECT COUNT(*) FROM {}", ddl::quote_ident(table));
+            let mut params = Vec::new();
 
-        if let Some(filter) = filters {
-            let (where_clause, where_params) = build_where_clause(&filter)?;
-            sql.push_str(&format!(" WHERE {}", where_clause));
-            params = where_params;
-        }
+            if let Some(filter) = filters {
+                let (where_clause, where_params) = build_where_clause(conn, table, &filter)?;
+                sql.push_str(&format!(" WHERE {}", where_clause));
+                params = where_params;
+            }
 
-        debug!("Executing SQL: {}", sql);
-        debug!("Params: {:?}", params);
+            debug!(%sql, ?params, "Executing SQL");
 
-        let param_refs: Vec<&dyn rusqlite::ToSql> =
-            params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+            let param_refs: Vec<&dyn rusqlite::ToSql> =
+                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
 
-        let count: i64 = conn.query_row(&sql, param_refs.as_slice(), |row| row.get(0))?;
-        let count = count as usize;
+            let count: i64 = conn.query_row(&sql, param_refs.as_slice(), |row| row.get(0))?;
+            let count = count as usize;
 
-        debug!("Count returned {} records", count);
-        Ok(count)
+            debug!("Count returned {} records", count);
+            Ok(count)
+        })
+        .await
     }
 
     async fn insert(&self, table: &str, data: serde_json::Value) -> anyhow::Result<i64> {
+        self.check_table_access(table)?;
+
         if self.readonly {
-            return Err(anyhow!("Cannot insert in read-only mode"));
+            return Err(DbError::Readonly("insert").into());
         }
 
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-
-        if let serde_json::Value::Object(obj) = data {
-            let columns: Vec<&String> = obj.keys().collect();
-            let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
-            let sql = format!(
-                "INSERT INTO {} ({}) VALUES ({})",
-                table,
-                columns
-                    .iter()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<_>>()
-                    .join(", "),
-                placeholders.join(", ")
-            );
+        let table = table.to_string();
+        self.run(move |_, conn| {
+            let table = &table;
+            if let serde_json::Value::Object(obj) = data {
+                check_allowed_values(&get_column_metadata(conn, table)?, table, &obj)?;
+
+                let columns: Vec<String> = obj.keys().map(|k| ddl::quote_ident(k)).collect();
+                let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
+                let sql = format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    ddl::quote_ident(table),
+                    columns.join(", "),
+                    placeholders.join(", ")
+                );
 
-            let mut params = Vec::new();
-            for val in obj.values() {
-                params.push(json_value_to_sqlite(val));
-            }
+                let mut params = Vec::new();
+                for val in obj.values() {
+                    params.push(json_value_to_sqlite(val));
+                }
 
-            debug!("Inserting into table '{}'", table);
-            debug!("SQL: {}", sql);
+                debug!("Inserting into table '{}'", table);
+                debug!(%sql, "Executing SQL");
 
-            let param_refs: Vec<&dyn rusqlite::ToSql> =
-                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
-            conn.execute(&sql, param_refs.as_slice())?;
-            let id = conn.last_insert_rowid();
-            debug!("Inserted row with ID: {}", id);
+                let param_refs: Vec<&dyn rusqlite::ToSql> =
+                    params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+                conn.execute(&sql, param_refs.as_slice())?;
+                let id = conn.last_insert_rowid();
+                debug!("Inserted row with ID: {}", id);
 
-            Ok(id)
-        } else {
-            Err(anyhow!("Data must be a JSON object"))
-        }
+                Ok(id)
+            } else {
+                Err(DbError::InvalidArgument("Data must be a JSON object".to_string()).into())
+            }
+        })
+        .await
     }
 
     async fn update(
@@ -729,108 +2182,85 @@ impl DatabaseAdapter for SqliteDatabase {
         pk_value: i64,
         data: serde_json::Value,
     ) -> anyhow::Result<usize> {
+        self.check_table_access(table)?;
+
         if self.readonly {
-            return Err(anyhow!("Cannot update in read-only mode"));
+            return Err(DbError::Readonly("update").into());
         }
 
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+        let table = table.to_string();
+        self.run(move |_, conn| {
+            let table = &table;
+            let pk_column = primary_key_column(conn, table)?;
+
+            if let serde_json::Value::Object(obj) = data {
+                check_allowed_values(&get_column_metadata(conn, table)?, table, &obj)?;
+
+                let sets: Vec<String> =
+                    obj.keys().map(|k| format!("{} = ?", ddl::quote_ident(k))).collect();
+                let sql = format!(
+                    "UPDATE {} SET {} WHERE {} = ?",
+                    ddl::quote_ident(table),
+                    sets.join(", "),
+                    ddl::quote_ident(&pk_column)
+                );
 
-        // Get primary key column
-        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
-        let mut rows = stmt.query([])?;
-        let mut primary_keys = Vec::new();
+                let mut params = Vec::new();
+                for val in obj.values() {
+                    params.push(json_value_to_sqlite(val));
+                }
+                params.push(rusqlite::types::Value::Integer(pk_value));
 
-        while let Some(row) = rows.next()? {
-            let is_pk: i32 = row.get(5)?;
-            if is_pk > 0 {
-                let name: String = row.get(1)?;
-                primary_keys.push(name);
+                debug!(
+                    "Updating table '{}' where {} = {}",
+                    table, pk_column, pk_value
+                );
+                debug!(%sql, "Executing SQL");
+
+                let param_refs: Vec<&dyn rusqlite::ToSql> =
+                    params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+                let affected = conn.execute(&sql, param_refs.as_slice())?;
+                debug!("Updated {} rows", affected);
+
+                Ok(affected)
+            } else {
+                Err(DbError::InvalidArgument("Data must be a JSON object".to_string()).into())
             }
+        })
+        .await
+    }
+
+    async fn delete(&self, table: &str, pk_value: i64) -> anyhow::Result<usize> {
+        self.check_table_access(table)?;
+
+        if self.readonly {
+            return Err(DbError::Readonly("delete").into());
         }
 
-        let pk_column = if primary_keys.len() == 1 {
-            primary_keys[0].clone()
-        } else {
-            "rowid".to_string()
-        };
+        let table = table.to_string();
+        self.run(move |_, conn| {
+            let table = &table;
+            let pk_column = primary_key_column(conn, table)?;
 
-        if let serde_json::Value::Object(obj) = data {
-            let sets: Vec<String> = obj.keys().map(|k| format!("{} = ?", k)).collect();
             let sql = format!(
-                "UPDATE {} SET {} WHERE {} = ?",
-                table,
-                sets.join(", "),
-                pk_column
+                "DELETE FROM {} WHERE {} = ?",
+                ddl::quote_ident(table),
+                ddl::quote_ident(&pk_column)
             );
-
-            let mut params = Vec::new();
-            for val in obj.values() {
-                params.push(json_value_to_sqlite(val));
-            }
-            params.push(rusqlite::types::Value::Integer(pk_value));
-
             debug!(
-                "Updating table '{}' where {} = {}",
+                "Deleting from table '{}' where {} = {}",
                 table, pk_column, pk_value
             );
-            debug!("SQL: {}", sql);
+            debug!(%sql, "Executing SQL");
 
-            let param_refs: Vec<&dyn rusqlite::ToSql> =
-                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+            let pk_param = rusqlite::types::Value::Integer(pk_value);
+            let param_refs: Vec<&dyn rusqlite::ToSql> = vec![&pk_param];
             let affected = conn.execute(&sql, param_refs.as_slice())?;
-            debug!("Updated {} rows", affected);
+            debug!("Deleted {} rows", affected);
 
             Ok(affected)
-        } else {
-            Err(anyhow!("Data must be a JSON object"))
-        }
-    }
-
-    async fn delete(&self, table: &str, pk_value: i64) -> anyhow::Result<usize> {
-        if self.readonly {
-            return Err(anyhow!("Cannot delete in read-only mode"));
-        }
-
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-
-        // Get primary key column
-        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
-        let mut rows = stmt.query([])?;
-        let mut primary_keys = Vec::new();
-
-        while let Some(row) = rows.next()? {
-            let is_pk: i32 = row.get(5)?;
-            if is_pk > 0 {
-                let name: String = row.get(1)?;
-                primary_keys.push(name);
-            }
-        }
-
-        let pk_column = if primary_keys.len() == 1 {
-            primary_keys[0].clone()
-        } else {
-            "rowid".to_string()
-        };
-
-        let sql = format!("DELETE FROM {} WHERE {} = ?", table, pk_column);
-        debug!(
-            "Deleting from table '{}' where {} = {}",
-            table, pk_column, pk_value
-        );
-        debug!("SQL: {}", sql);
-
-        let pk_param = rusqlite::types::Value::Integer(pk_value);
-        let param_refs: Vec<&dyn rusqlite::ToSql> = vec![&pk_param];
-        let affected = conn.execute(&sql, param_refs.as_slice())?;
-        debug!("Deleted {} rows", affected);
-
-        Ok(affected)
+        })
+        .await
     }
 
     async fn batch_insert(
@@ -839,8 +2269,10 @@ impl DatabaseAdapter for SqliteDatabase {
         items: Vec<serde_json::Value>,
         batch_size: usize,
     ) -> anyhow::Result<BatchResult> {
+        self.check_table_access(table)?;
+
         if self.readonly {
-            return Err(anyhow!("Cannot insert in read-only mode"));
+            return Err(DbError::Readonly("insert").into());
         }
 
         if items.is_empty() {
@@ -858,6 +2290,7 @@ impl DatabaseAdapter for SqliteDatabase {
         let mut failed = 0;
         let mut errors = Vec::new();
         let mut inserted_ids = Vec::new();
+        let mut processed = 0;
 
         info!(
             "Starting batch insert of {} items into table '{}' with batch size {}",
@@ -865,55 +2298,71 @@ impl DatabaseAdapter for SqliteDatabase {
         );
 
         for batch in items.chunks(batch_size) {
-            let conn = self
-                .conn
-                .lock()
-                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-            let tx = conn.unchecked_transaction()?;
-            let batch_succeeded = batch.len();
-            let mut batch_errors = Vec::new();
-
-            for item in batch {
-                if let serde_json::Value::Object(obj) = item {
-                    let columns: Vec<&String> = obj.keys().collect();
-                    let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
-                    let sql = format!(
-                        "INSERT INTO {} ({}) VALUES ({})",
-                        table,
-                        columns
-                            .iter()
-                            .map(|s| s.as_str())
-                            .collect::<Vec<_>>()
-                            .join(", "),
-                        placeholders.join(", ")
-                    );
-
-                    let mut params = Vec::new();
-                    for val in obj.values() {
-                        params.push(json_value_to_sqlite(val));
+            let table = table.to_string();
+            let batch_len = batch.len();
+            let batch = batch.to_vec();
+            let (batch_ids, batch_errors) = self
+                .run(move |_, conn| {
+                    let tx = conn.unchecked_transaction()?;
+                    let mut batch_ids = Vec::new();
+                    let mut batch_errors = Vec::new();
+                    let metadata = get_column_metadata(&tx, &table)?;
+
+                    for item in &batch {
+                        if let serde_json::Value::Object(obj) = item {
+                            if let Err(e) = check_allowed_values(&metadata, &table, obj) {
+                                batch_errors.push(format!("Insert failed: {}", e));
+                                continue;
+                            }
+
+                            let columns: Vec<String> =
+                                obj.keys().map(|k| ddl::quote_ident(k)).collect();
+                            let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
+                            let sql = format!(
+                                "INSERT INTO {} ({}) VALUES ({})",
+                                ddl::quote_ident(&table),
+                                columns.join(", "),
+                                placeholders.join(", ")
+                            );
+
+                            let mut params = Vec::new();
+                            for val in obj.values() {
+                                params.push(json_value_to_sqlite(val));
+                            }
+
+                            let param_refs: Vec<&dyn rusqlite::ToSql> =
+                                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+                            match tx.execute(&sql, param_refs.as_slice()) {
+                                Ok(_) => {
+                                    batch_ids.push(tx.last_insert_rowid());
+                                }
+                                Err(e) => {
+                                    batch_errors.push(format!("Insert failed: {}", e));
+                                }
+                            }
+                        }
                     }
 
-                    let param_refs: Vec<&dyn rusqlite::ToSql> =
-                        params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
-                    match tx.execute(&sql, param_refs.as_slice()) {
-                        Ok(_) => {
-                            inserted_ids.push(tx.last_insert_rowid());
-                        }
-                        Err(e) => {
-                            batch_errors.push(format!("Insert failed: {}", e));
-                            failed += 1;
-                        }
+                    if batch_errors.is_empty() {
+                        tx.commit()?;
+                    } else {
+                        tx.rollback()?;
                     }
-                }
-            }
+                    Ok((batch_ids, batch_errors))
+                })
+                .await?;
 
+            inserted_ids.extend(batch_ids);
             if batch_errors.is_empty() {
-                tx.commit()?;
-                succeeded += batch_succeeded;
+                succeeded += batch_len;
             } else {
-                tx.rollback()?;
+                failed += batch_errors.len();
                 errors.extend(batch_errors);
             }
+            processed += batch_len;
+            progress::report(processed, total, || {
+                format!("Processed {} of {} rows", processed, total)
+            });
         }
 
         info!(
@@ -936,8 +2385,10 @@ impl DatabaseAdapter for SqliteDatabase {
         updates: Vec<(i64, serde_json::Value)>,
         batch_size: usize,
     ) -> anyhow::Result<BatchResult> {
+        self.check_table_access(table)?;
+
         if self.readonly {
-            return Err(anyhow!("Cannot update in read-only mode"));
+            return Err(DbError::Readonly("update").into());
         }
 
         if updates.is_empty() {
@@ -954,81 +2405,80 @@ impl DatabaseAdapter for SqliteDatabase {
         let mut succeeded = 0;
         let mut failed = 0;
         let mut errors = Vec::new();
+        let mut processed = 0;
 
         info!(
             "Starting batch update of {} items in table '{}' with batch size {}",
             total, table, batch_size
         );
 
-        // Get primary key column first
         let pk_column = {
-            let conn = self
-                .conn
-                .lock()
-                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-            let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
-            let mut rows = stmt.query([])?;
-            let mut primary_keys = Vec::new();
-
-            while let Some(row) = rows.next()? {
-                let is_pk: i32 = row.get(5)?;
-                if is_pk > 0 {
-                    let name: String = row.get(1)?;
-                    primary_keys.push(name);
-                }
-            }
-
-            if primary_keys.len() == 1 {
-                primary_keys[0].clone()
-            } else {
-                "rowid".to_string()
-            }
-        }; // conn is dropped here, releasing the lock
+            let table = table.to_string();
+            self.run(move |_, conn| primary_key_column(conn, &table)).await?
+        };
 
         for batch in updates.chunks(batch_size) {
-            let conn = self
-                .conn
-                .lock()
-                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-            let tx = conn.unchecked_transaction()?;
-            let batch_succeeded = batch.len();
-            let mut batch_errors = Vec::new();
-
-            for (pk_value, data) in batch {
-                if let serde_json::Value::Object(obj) = data {
-                    let sets: Vec<String> = obj.keys().map(|k| format!("{} = ?", k)).collect();
-                    let sql = format!(
-                        "UPDATE {} SET {} WHERE {} = ?",
-                        table,
-                        sets.join(", "),
-                        pk_column
-                    );
-
-                    let mut params = Vec::new();
-                    for val in obj.values() {
-                        params.push(json_value_to_sqlite(val));
-                    }
-                    params.push(rusqlite::types::Value::Integer(*pk_value));
-
-                    let param_refs: Vec<&dyn rusqlite::ToSql> =
-                        params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
-                    match tx.execute(&sql, param_refs.as_slice()) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            batch_errors.push(format!("Update failed for ID {}: {}", pk_value, e));
-                            failed += 1;
+            let table = table.to_string();
+            let pk_column = pk_column.clone();
+            let batch_len = batch.len();
+            let batch = batch.to_vec();
+            let batch_errors = self
+                .run(move |_, conn| {
+                    let tx = conn.unchecked_transaction()?;
+                    let mut batch_errors = Vec::new();
+                    let metadata = get_column_metadata(&tx, &table)?;
+
+                    for (pk_value, data) in &batch {
+                        if let serde_json::Value::Object(obj) = data {
+                            if let Err(e) = check_allowed_values(&metadata, &table, obj) {
+                                batch_errors.push(format!("Update failed for ID {}: {}", pk_value, e));
+                                continue;
+                            }
+
+                            let sets: Vec<String> = obj
+                                .keys()
+                                .map(|k| format!("{} = ?", ddl::quote_ident(k)))
+                                .collect();
+                            let sql = format!(
+                                "UPDATE {} SET {} WHERE {} = ?",
+                                ddl::quote_ident(&table),
+                                sets.join(", "),
+                                ddl::quote_ident(&pk_column)
+                            );
+
+                            let mut params = Vec::new();
+                            for val in obj.values() {
+                                params.push(json_value_to_sqlite(val));
+                            }
+                            params.push(rusqlite::types::Value::Integer(*pk_value));
+
+                            let param_refs: Vec<&dyn rusqlite::ToSql> =
+                                params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+                            if let Err(e) = tx.execute(&sql, param_refs.as_slice()) {
+                                batch_errors.push(format!("Update failed for ID {}: {}", pk_value, e));
+                            }
                         }
                     }
-                }
-            }
+
+                    if batch_errors.is_empty() {
+                        tx.commit()?;
+                    } else {
+                        tx.rollback()?;
+                    }
+                    Ok(batch_errors)
+                })
+                .await?;
 
             if batch_errors.is_empty() {
-                tx.commit()?;
-                succeeded += batch_succeeded;
+                succeeded += batch_len;
             } else {
-                tx.rollback()?;
+                failed += batch_errors.len();
                 errors.extend(batch_errors);
             }
+            processed += batch_len;
+            progress::report(processed, total, || {
+                format!("Processed {} of {} updates", processed, total)
+            });
         }
 
         info!(
@@ -1046,8 +2496,10 @@ impl DatabaseAdapter for SqliteDatabase {
     }
 
     async fn batch_delete(&self, table: &str, ids: Vec<i64>) -> anyhow::Result<usize> {
+        self.check_table_access(table)?;
+
         if self.readonly {
-            return Err(anyhow!("Cannot delete in read-only mode"));
+            return Err(DbError::Readonly("delete").into());
         }
 
         if ids.is_empty() {
@@ -1060,53 +2512,29 @@ impl DatabaseAdapter for SqliteDatabase {
             table
         );
 
-        // Get primary key column first
-        let pk_column = {
-            let conn = self
-                .conn
-                .lock()
-                .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-            let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
-            let mut rows = stmt.query([])?;
-            let mut primary_keys = Vec::new();
-
-            while let Some(row) = rows.next()? {
-                let is_pk: i32 = row.get(5)?;
-                if is_pk > 0 {
-                    let name: String = row.get(1)?;
-                    primary_keys.push(name);
-                }
-            }
-
-            if primary_keys.len() == 1 {
-                primary_keys[0].clone()
-            } else {
-                "rowid".to_string()
-            }
-        }; // conn is dropped here, releasing the lock
-
-        let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
-        let sql = format!(
-            "DELETE FROM {} WHERE {} IN ({})",
-            table,
-            pk_column,
-            placeholders.join(", ")
-        );
-
-        debug!("Batch delete SQL: {}", sql);
+        let table = table.to_string();
+        let affected = self
+            .run(move |_, conn| {
+                let pk_column = primary_key_column(conn, &table)?;
+                let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
+                let sql = format!(
+                    "DELETE FROM {} WHERE {} IN ({})",
+                    ddl::quote_ident(&table),
+                    ddl::quote_ident(&pk_column),
+                    placeholders.join(", ")
+                );
 
-        let params: Vec<rusqlite::types::Value> = ids
-            .iter()
-            .map(|id| rusqlite::types::Value::Integer(*id))
-            .collect();
+                debug!(%sql, "Executing batch delete");
 
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-        let param_refs: Vec<&dyn rusqlite::ToSql> =
-            params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
-        let affected = conn.execute(&sql, param_refs.as_slice())?;
+                let params: Vec<rusqlite::types::Value> = ids
+                    .iter()
+                    .map(|id| rusqlite::types::Value::Integer(*id))
+                    .collect();
+                let param_refs: Vec<&dyn rusqlite::ToSql> =
+                    params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+                Ok(conn.execute(&sql, param_refs.as_slice())?)
+            })
+            .await?;
         info!("Batch deleted {} rows", affected);
 
         Ok(affected)
@@ -1114,14 +2542,15 @@ impl DatabaseAdapter for SqliteDatabase {
 
     async fn set_table_comment(&self, table: &str, desc: &str) -> anyhow::Result<()> {
         if self.readonly {
-            return Err(anyhow!("Cannot set table comment in read-only mode"));
+            return Err(DbError::Readonly("set table comment").into());
         }
 
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-        self.set_table_comment_internal(&conn, table, desc)
+        let table = table.to_string();
+        let desc = desc.to_string();
+        self.run(move |db, conn| {
+            db.set_table_comment_internal(conn, &table, &desc)
+        })
+        .await
     }
 
     async fn set_column_comment(
@@ -1131,17 +2560,288 @@ impl DatabaseAdapter for SqliteDatabase {
         desc: &str,
     ) -> anyhow::Result<()> {
         if self.readonly {
-            return Err(anyhow!("Cannot set column comment in read-only mode"));
+            return Err(DbError::Readonly("set column comment").into());
+        }
+
+        let table = table.to_string();
+        let column = column.to_string();
+        let desc = desc.to_string();
+        self.run(move |db, conn| {
+            db.set_column_comment_internal(conn, &table, &column, &desc)
+        })
+        .await
+    }
+
+    async fn set_column_metadata(
+        &self,
+        table: &str,
+        column: &str,
+        metadata: ColumnMetadata,
+    ) -> anyhow::Result<()> {
+        if self.readonly {
+            return Err(DbError::Readonly("set column metadata").into());
+        }
+
+        let table = table.to_string();
+        let column = column.to_string();
+        self.run(move |db, conn| {
+            db.set_column_metadata_internal(conn, &table, &column, &metadata)
+        })
+        .await
+    }
+
+    async fn import_comments(
+        &self,
+        document: CommentDocument,
+    ) -> anyhow::Result<ImportCommentsResult> {
+        if self.readonly {
+            return Err(DbError::Readonly("import comments").into());
+        }
+
+        self.run(move |db, conn| {
+            let tx = conn.unchecked_transaction()?;
+            let result = db.import_comments_internal(&tx, &document)?;
+            tx.commit()?;
+
+            info!(
+                "Imported comments for {} tables and {} columns",
+                result.tables_updated, result.columns_updated
+            );
+            Ok(result)
+        })
+        .await
+    }
+
+    async fn create_table(&self, definition: TableDefinition) -> anyhow::Result<TableSchema> {
+        self.check_table_access(&definition.name)?;
+
+        if self.readonly {
+            return Err(DbError::Readonly("create table").into());
+        }
+
+        self.run(move |db, conn| {
+            db.create_table_internal(conn, &definition)?;
+            db.get_schema_internal(conn, &definition.name)
+        })
+        .await
+    }
+
+    async fn add_column(
+        &self,
+        table: &str,
+        column: ColumnDefinition,
+    ) -> anyhow::Result<TableSchema> {
+        self.check_table_access(table)?;
+
+        if self.readonly {
+            return Err(DbError::Readonly("add column").into());
         }
 
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
-        self.set_column_comment_internal(&conn, table, column, desc)
+        let table = table.to_string();
+        self.run(move |db, conn| {
+            let table = &table;
+            db.add_column_internal(conn, table, &column)?;
+            db.get_schema_internal(conn, table)
+        })
+        .await
+    }
+
+    async fn create_index(&self, index: IndexDefinition) -> anyhow::Result<TableSchema> {
+        self.check_table_access(&index.table)?;
+
+        if self.readonly {
+            return Err(DbError::Readonly("create index").into());
+        }
+
+        self.run(move |db, conn| {
+            db.create_index_internal(conn, &index)?;
+            db.get_schema_internal(conn, &index.table)
+        })
+        .await
+    }
+
+    async fn drop_index(
+        &self,
+        name: &str,
+        if_exists: bool,
+    ) -> anyhow::Result<Option<TableSchema>> {
+        if self.readonly {
+            return Err(DbError::Readonly("drop index").into());
+        }
+
+        let name = name.to_string();
+        self.run(move |db, conn| {
+            let name = &name;
+            let table: Option<String> = conn
+                .query_row(
+                    "SELECT tbl_name FROM sqlite_master WHERE type='index' AND name=?",
+                    [name],
+                    |row| row.get(0),
+                )
+                .ok();
+            if let Some(table) = &table {
+                db.check_table_access(table)?;
+            }
+
+            match db.drop_index_internal(conn, name, if_exists)? {
+                Some(table) => Ok(Some(db.get_schema_internal(conn, &table)?)),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn alter_table(
+        &self,
+        table: &str,
+        operation: AlterTableOperation,
+    ) -> anyhow::Result<AlterTableResult> {
+        self.check_table_access(table)?;
+        if let AlterTableOperation::RenameTable { new_name } = &operation {
+            self.check_table_access(new_name)?;
+        }
+
+        if self.readonly {
+            return Err(DbError::Readonly("alter table").into());
+        }
+
+        let table = table.to_string();
+        self.run(move |db, conn| {
+            let table = &table;
+            let (table, strategy, removed) = db.with_foreign_keys_disabled(conn, |conn| {
+                db.alter_table_internal(conn, table, &operation)
+            })?;
+
+            Ok(AlterTableResult {
+                strategy,
+                removed,
+                schema: db.get_schema_internal(conn, &table)?,
+            })
+        })
+        .await
+    }
+
+    async fn migrate(
+        &self,
+        dir: &Path,
+        command: MigrationCommand,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> anyhow::Result<MigrationReport> {
+        if self.readonly && command != MigrationCommand::Status && !dry_run {
+            return Err(DbError::Readonly("run migrations").into());
+        }
+
+        let dir = dir.to_path_buf();
+        self.run(move |db, conn| {
+            db.migrate_internal(conn, &dir, command, target, dry_run)
+        })
+        .await
+    }
+
+    async fn schema_diff(
+        &self,
+        source: Option<&Path>,
+        target: &Path,
+        include_sql: bool,
+    ) -> anyhow::Result<SchemaDiff> {
+        let source = source.map(Path::to_path_buf);
+        let target = target.to_path_buf();
+        self.run(move |_, conn| {
+            let open = |path: &Path| {
+                rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+                    .map_err(|e| anyhow!("Failed to open database {:?}: {}", path, e))
+            };
+            let target = open(&target)?;
+
+            match source {
+                Some(path) => schema_diff::diff(&open(&path)?, &target, include_sql),
+                None => schema_diff::diff(conn, &target, include_sql),
+            }
+        })
+        .await
     }
 
     async fn is_readonly(&self) -> bool {
         self.readonly
     }
+
+    async fn database_name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn subscribe_changes(&self) -> broadcast::Receiver<DataChange> {
+        self.changes.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(sql: &str) -> SqliteDatabase {
+        let db = SqliteDatabase::new(Path::new(":memory:"), false).unwrap();
+        db.conn.lock().unwrap().execute_batch(sql).unwrap();
+        db
+    }
+
+    fn change_type(column: &str, data_type: &str) -> AlterTableOperation {
+        AlterTableOperation::ChangeType {
+            column: column.to_string(),
+            data_type: data_type.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuild_keeps_rowids_without_integer_primary_key() {
+        let db = database(
+            "CREATE TABLE t (a TEXT, b INTEGER);
+             INSERT INTO t VALUES ('x', 1), ('y', 2), ('z', 3);
+             DELETE FROM t WHERE b = 1;",
+        );
+
+        let result = db.alter_table("t", change_type("b", "REAL")).await.unwrap();
+        assert!(matches!(result.strategy, AlterStrategy::Rebuild));
+
+        let conn = db.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT rowid, a FROM t ORDER BY rowid")
+            .unwrap();
+        let rows: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows, vec![(2, "y".to_string()), (3, "z".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn rebuild_refuses_features_the_definition_would_drop() {
+        let create = "CREATE TABLE n (a TEXT COLLATE NOCASE, b INTEGER, \
+                      c INTEGER GENERATED ALWAYS AS (b * 2) VIRTUAL)";
+        let db = database(create);
+
+        let error = db
+            .alter_table("n", change_type("b", "REAL"))
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<DbError>().unwrap();
+        assert!(matches!(error, DbError::Unsupported(_)), "{}", error);
+
+        let conn = db.conn.lock().unwrap();
+        assert_eq!(get_create_sql(&conn, "n").unwrap(), create);
+    }
+
+    #[test]
+    fn unsupported_features_ignore_expressions_and_column_names() {
+        let features = ddl::unsupported_table_features(
+            "CREATE TABLE t (generated TEXT, conflict TEXT CHECK (conflict COLLATE NOCASE <> 'x'))",
+        );
+        assert!(features.is_empty(), "{:?}", features);
+
+        let features = ddl::unsupported_table_features(
+            "CREATE TABLE t (id TEXT PRIMARY KEY ON CONFLICT REPLACE) WITHOUT ROWID, STRICT",
+        );
+        assert_eq!(features, ["ON CONFLICT clauses", "WITHOUT ROWID", "STRICT"]);
+    }
 }
@@ -0,0 +1,414 @@
+//! MCP Streamable HTTP transport. Clients POST JSON-RPC messages to `/mcp`
+//! and get the responses back as JSON; server notifications (resource
+//! updates, progress) are delivered on an optional SSE stream opened with GET.
+//! Each client gets its own session, identified by the `Mcp-Session-Id`
+//! header, with its own dispatcher and subscriptions. When tokens are
+//! configured every request needs `Authorization: Bearer <token>`, and a
+//! session only accepts the token that created it.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use tracing::{info, Instrument};
+
+use crate::auth::{self, Scope, TokenStore};
+use crate::db::DatabaseAdapter;
+use crate::jsonrpc::{self, RpcError};
+use crate::resources;
+use crate::server::{Server, SUPPORTED_PROTOCOL_VERSIONS};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// An HTTP-level rejection, sent before any JSON-RPC processing.
+type Rejection = (StatusCode, &'static str);
+
+/// Notifications buffered for a slow SSE client before it starts missing them.
+const NOTIFICATION_BUFFER: usize = 256;
+
+/// Sessions without requests or an open SSE stream for this long are
+/// terminated, so clients that never send DELETE do not pile up.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often idle sessions are looked for.
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Session {
+    server: Server,
+    /// Digest of the token that created the session
+    token: Option<[u8; 32]>,
+    notifications: broadcast::Sender<serde_json::Value>,
+    tasks: Vec<JoinHandle<()>>,
+    last_used: Mutex<Instant>,
+}
+
+impl Session {
+    fn new(
+        db: Arc<dyn DatabaseAdapter>,
+        credentials: Credentials,
+        confirm_threshold: usize,
+    ) -> Self {
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel();
+        let (notifications, _) = broadcast::channel(NOTIFICATION_BUFFER);
+
+        let subscriptions = resources::Subscriptions::default();
+        let server = Server::new(db.clone(), subscriptions.clone(), outgoing.clone())
+            .with_scope(credentials.scope)
+            .with_confirm_threshold(confirm_threshold);
+        let forwarder = tokio::spawn(
+            resources::forward_changes(db, subscriptions, outgoing).instrument(server.span()),
+        );
+
+        // Without an open SSE stream notifications are dropped, and requests
+        // to the client fail at once instead of waiting for an answer
+        let sender = notifications.clone();
+        let requester = server.clone();
+        let pump = tokio::spawn(
+            async move {
+                while let Some(message) = outgoing_rx.recv().await {
+                    let id = message.get("method").and(message.get("id")).cloned();
+                    if sender.send(message).is_err() {
+                        if let Some(id) = id {
+                            let error = RpcError {
+                                code: RpcError::INTERNAL_ERROR,
+                                message: "No SSE stream open to reach the client".to_string(),
+                            };
+                            let id = serde_json::from_value(id).ok();
+                            let response = jsonrpc::Response::error(id, error);
+                            if let Ok(response) = serde_json::to_value(response) {
+                                requester.handle_json(response).await;
+                            }
+                        }
+                    }
+                }
+            }
+            .instrument(server.span()),
+        );
+
+        Self {
+            server,
+            token: credentials.token,
+            notifications,
+            tasks: vec![forwarder, pump],
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+    }
+
+    /// An open SSE stream keeps the session alive however long it is quiet.
+    fn is_idle(&self) -> bool {
+        self.notifications.receiver_count() == 0
+            && self
+                .last_used
+                .lock()
+                .is_ok_and(|last_used| last_used.elapsed() > SESSION_IDLE_TIMEOUT)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Who is calling: the digest of the bearer token and the scope it grants.
+#[derive(Debug, Clone, Copy)]
+struct Credentials {
+    token: Option<[u8; 32]>,
+    scope: Scope,
+}
+
+#[derive(Clone)]
+struct AppState {
+    db: Arc<dyn DatabaseAdapter>,
+    sessions: Arc<Mutex<HashMap<String, Arc<Session>>>>,
+    tokens: Option<Arc<TokenStore>>,
+    confirm_threshold: usize,
+}
+
+impl AppState {
+    /// Without configured tokens every caller has full access.
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Credentials, Rejection> {
+        let Some(tokens) = &self.tokens else {
+            return Ok(Credentials {
+                token: None,
+                scope: Scope::Admin,
+            });
+        };
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing bearer token"))?;
+        let scope = tokens
+            .scope(token)
+            .ok_or((StatusCode::UNAUTHORIZED, "Invalid bearer token"))?;
+        Ok(Credentials {
+            token: Some(auth::digest(token)),
+            scope,
+        })
+    }
+
+    fn create_session(&self, credentials: Credentials) -> anyhow::Result<(String, Arc<Session>)> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = Arc::new(Session::new(
+            self.db.clone(),
+            credentials,
+            self.confirm_threshold,
+        ));
+        self.sessions
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Failed to lock sessions: {}", e))?
+            .insert(id.clone(), session.clone());
+        session.server.span().in_scope(|| {
+            info!("Created HTTP session ({} scope)", credentials.scope.as_str());
+        });
+        Ok((id, session))
+    }
+
+    /// The session named by the request headers. Requests without a session
+    /// id are rejected with 400, unknown or terminated sessions with 404, and
+    /// sessions created with another token with 403.
+    fn session(
+        &self,
+        headers: &HeaderMap,
+        credentials: Credentials,
+    ) -> Result<Arc<Session>, Rejection> {
+        let id = headers
+            .get(SESSION_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .ok_or((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"))?;
+
+        let session = self
+            .sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| sessions.get(id).cloned())
+            .ok_or((StatusCode::NOT_FOUND, "Session not found"))?;
+        if session.token != credentials.token {
+            return Err((StatusCode::FORBIDDEN, "Session belongs to another token"));
+        }
+        session.touch();
+        Ok(session)
+    }
+
+    /// Terminate the sessions that have been idle too long.
+    fn expire_sessions(&self) {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return;
+        };
+        sessions.retain(|_, session| {
+            if !session.is_idle() {
+                return true;
+            }
+            session.server.span().in_scope(|| info!("Expired idle HTTP session"));
+            false
+        });
+    }
+}
+
+pub async fn serve(
+    db: Arc<dyn DatabaseAdapter>,
+    listen: SocketAddr,
+    tokens: Option<TokenStore>,
+    confirm_threshold: usize,
+) -> anyhow::Result<()> {
+    if tokens.is_none() {
+        tracing::warn!(
+            "No tokens configured; every client on {} has full access",
+            listen
+        );
+    }
+    let state = AppState {
+        db,
+        sessions: Arc::default(),
+        tokens: tokens.map(Arc::new),
+        confirm_threshold,
+    };
+    let app = Router::new()
+        .route(
+            "/mcp",
+            post(handle_post).get(handle_get).delete(handle_delete),
+        )
+        .with_state(state.clone());
+
+    let sweeper = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.expire_sessions();
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("Listening on http://{}/mcp", listener.local_addr()?);
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutting down HTTP server");
+        })
+        .await?;
+    sweeper.abort();
+    Ok(())
+}
+
+fn reject((status, message): Rejection) -> Response {
+    let mut response = (status, message).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Reject requests whose Origin is not a local page, so that web sites
+/// cannot reach the server through the browser (DNS rebinding).
+fn check_origin(headers: &HeaderMap) -> Result<(), Rejection> {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return Ok(());
+    };
+    let host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, rest)| rest.split('/').next().unwrap_or(rest))
+        .map(|authority| match authority.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or(ipv6),
+            None => authority.split(':').next().unwrap_or(authority),
+        });
+
+    match host {
+        Some("localhost" | "127.0.0.1" | "::1") => Ok(()),
+        _ => Err((StatusCode::FORBIDDEN, "Origin not allowed")),
+    }
+}
+
+fn check_protocol_version(headers: &HeaderMap) -> Result<(), Rejection> {
+    match headers.get(PROTOCOL_VERSION_HEADER).map(|v| v.to_str()) {
+        None => Ok(()),
+        Some(Ok(version)) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => Ok(()),
+        Some(_) => Err((StatusCode::BAD_REQUEST, "Unsupported MCP-Protocol-Version")),
+    }
+}
+
+fn is_initialize(message: &serde_json::Value) -> bool {
+    message.get("method").and_then(|m| m.as_str()) == Some("initialize")
+}
+
+async fn handle_post(State(state): State<AppState>, headers: HeaderMap, body: String) -> Response {
+    let credentials = match check_origin(&headers)
+        .and_then(|_| check_protocol_version(&headers))
+        .and_then(|_| state.authenticate(&headers))
+    {
+        Ok(credentials) => credentials,
+        Err(rejection) => return reject(rejection),
+    };
+
+    let message: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = jsonrpc::Response::error(None, RpcError::parse_error(e));
+            return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+        }
+    };
+
+    // initialize starts a new session; everything else must name one
+    let (session_id, session) = if is_initialize(&message) {
+        match state.create_session(credentials) {
+            Ok((id, session)) => (Some(id), session),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    } else {
+        match state.session(&headers, credentials) {
+            Ok(session) => (None, session),
+            Err(rejection) => return reject(rejection),
+        }
+    };
+
+    let reply = session.server.handle_json(message).await;
+    // A long request should not leave the session looking idle
+    session.touch();
+    let mut response = match reply {
+        Some(reply) => Json(reply).into_response(),
+        // Only notifications or responses
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    if let Some(id) = session_id.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert(SESSION_HEADER, id);
+    }
+    response
+}
+
+/// SSE stream of the session's server notifications.
+async fn handle_get(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let credentials = match check_origin(&headers).and_then(|_| state.authenticate(&headers)) {
+        Ok(credentials) => credentials,
+        Err(rejection) => return reject(rejection),
+    };
+    let accepts_sse = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    if !accepts_sse {
+        return (StatusCode::METHOD_NOT_ALLOWED, "GET requires Accept: text/event-stream").into_response();
+    }
+    let session = match state.session(&headers, credentials) {
+        Ok(session) => session,
+        Err(rejection) => return reject(rejection),
+    };
+
+    let stream = BroadcastStream::new(session.notifications.subscribe()).filter_map(|message| {
+        // A lagging client misses notifications rather than ending the stream
+        message
+            .ok()
+            .map(|message| Ok::<_, Infallible>(Event::default().data(message.to_string())))
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Terminate a session.
+async fn handle_delete(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let session = match check_origin(&headers)
+        .and_then(|_| state.authenticate(&headers))
+        .and_then(|credentials| state.session(&headers, credentials))
+    {
+        Ok(session) => session,
+        Err(rejection) => return reject(rejection),
+    };
+
+    // `session` has checked the header is present
+    let id = headers
+        .get(SESSION_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    if let Ok(mut sessions) = state.sessions.lock() {
+        sessions.remove(id);
+    }
+    session.server.span().in_scope(|| info!("Terminated HTTP session"));
+    StatusCode::NO_CONTENT.into_response()
+}
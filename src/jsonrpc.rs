@@ -0,0 +1,172 @@
+//! JSON-RPC 2.0 message types and error codes shared by the MCP method
+//! handlers.
+
+use serde::{Deserialize, Serialize};
+
+/// A JSON-RPC protocol error, sent to the client in `error` instead of a
+/// result.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// MCP-specific code for an unknown resource URI
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+    pub fn parse_error(message: impl std::fmt::Display) -> Self {
+        Self {
+            code: Self::PARSE_ERROR,
+            message: format!("Parse error: {}", message),
+        }
+    }
+
+    pub fn invalid_request(message: impl std::fmt::Display) -> Self {
+        Self {
+            code: Self::INVALID_REQUEST,
+            message: format!("Invalid request: {}", message),
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self {
+            code: Self::METHOD_NOT_FOUND,
+            message: format!("Method not found: {}", method),
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: Self::INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+
+    pub fn resource_not_found(uri: &str) -> Self {
+        Self {
+            code: Self::RESOURCE_NOT_FOUND,
+            message: format!("Resource not found: {}", uri),
+        }
+    }
+}
+
+/// Request id. MCP does not allow `null` ids, so a message without an id is
+/// always a notification.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestId::Number(n) => write!(f, "{}", n),
+            RequestId::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// An incoming request, or a notification when `id` is absent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Option<RequestId>,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+impl Request {
+    /// Validate a single message of a (possibly batched) payload. On failure
+    /// returns the error response to send back, with the message's id if it
+    /// had a usable one.
+    pub fn from_value(value: serde_json::Value) -> Result<Self, Response> {
+        let id = value
+            .get("id")
+            .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+        let request: Request = serde_json::from_value(value)
+            .map_err(|e| Response::error(id.clone(), RpcError::invalid_request(e)))?;
+        if request.jsonrpc != "2.0" {
+            return Err(Response::error(
+                id,
+                RpcError::invalid_request("jsonrpc must be \"2.0\""),
+            ));
+        }
+        Ok(request)
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorObject {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Response to a request. `id` is `null` only when the request's id could not
+/// be determined, e.g. after a parse error.
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Option<RequestId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+}
+
+impl Response {
+    pub fn success(id: Option<RequestId>, result: serde_json::Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Option<RequestId>, error: RpcError) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(ErrorObject {
+                code: error.code,
+                message: error.message,
+            }),
+        }
+    }
+}
+
+/// A server-to-client notification.
+pub fn notification(method: &str, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params
+    })
+}
+
+/// A server-to-client request. The client answers with a response carrying
+/// the same `id`.
+pub fn request(id: &RequestId, method: &str, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params
+    })
+}
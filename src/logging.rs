@@ -0,0 +1,233 @@
+//! MCP `logging` capability: a `tracing` layer that forwards events to
+//! clients as `notifications/message` once they pick a level with
+//! `logging/setLevel`. Events logged while handling a request go only to the
+//! session that sent it; everything else goes only to the session owning the
+//! transport, i.e. the stdio client. SQL text and bound values are redacted
+//! unless the server runs with `--log-sql`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::jsonrpc;
+
+/// Event fields holding SQL text or the values bound to it.
+const REDACTED_FIELDS: &[&str] = &["sql", "params", "filters"];
+
+/// Syslog severities used by MCP, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LoggingLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoggingLevel::Debug => "debug",
+            LoggingLevel::Info => "info",
+            LoggingLevel::Notice => "notice",
+            LoggingLevel::Warning => "warning",
+            LoggingLevel::Error => "error",
+            LoggingLevel::Critical => "critical",
+            LoggingLevel::Alert => "alert",
+            LoggingLevel::Emergency => "emergency",
+        }
+    }
+}
+
+impl From<&tracing::Level> for LoggingLevel {
+    fn from(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::ERROR => LoggingLevel::Error,
+            tracing::Level::WARN => LoggingLevel::Warning,
+            tracing::Level::INFO => LoggingLevel::Info,
+            _ => LoggingLevel::Debug,
+        }
+    }
+}
+
+/// Where a session wants its log messages, and from which level on. The
+/// sender is weak so a registered sink does not keep the session's writer open.
+struct Sink {
+    level: LoggingLevel,
+    /// Also receives events logged outside any session
+    owner: bool,
+    outgoing: mpsc::WeakUnboundedSender<serde_json::Value>,
+}
+
+// Global like the tracing subscriber itself; keyed by session id
+static SINKS: Mutex<BTreeMap<u64, Sink>> = Mutex::new(BTreeMap::new());
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+static LOG_SQL: AtomicBool = AtomicBool::new(false);
+
+/// A fresh id tying log events to the session whose requests produced them.
+pub fn new_session() -> u64 {
+    NEXT_SESSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Forward events at `level` and above to the session's `outgoing` channel;
+/// an `owner` session also gets events logged outside any session. Calling it
+/// again replaces the level.
+pub fn set_level(
+    session: u64,
+    level: LoggingLevel,
+    owner: bool,
+    outgoing: &mpsc::UnboundedSender<serde_json::Value>,
+) {
+    if let Ok(mut sinks) = SINKS.lock() {
+        let outgoing = outgoing.downgrade();
+        sinks.insert(
+            session,
+            Sink {
+                level,
+                owner,
+                outgoing,
+            },
+        );
+    }
+}
+
+/// Include SQL text and parameters in forwarded events.
+pub fn set_log_sql(enabled: bool) {
+    LOG_SQL.store(enabled, Ordering::Relaxed);
+}
+
+/// The layer to add to the subscriber. It only sees the server's own events,
+/// down to debug, whatever `RUST_LOG` says about stderr.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let targets = Targets::new().with_target(env!("CARGO_CRATE_NAME"), tracing::Level::DEBUG);
+    McpLayer.with_filter(targets)
+}
+
+/// Stored in the extensions of spans opened with a `session` field.
+struct SessionId(u64);
+
+struct McpLayer;
+
+impl<S> Layer<S> for McpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = SessionVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(session), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SessionId(session));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let level = LoggingLevel::from(event.metadata().level());
+        let Ok(mut sinks) = SINKS.lock() else {
+            return;
+        };
+        if !sinks.values().any(|sink| level >= sink.level) {
+            return;
+        }
+
+        let session = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .from_root()
+                .find_map(|span| span.extensions().get::<SessionId>().map(|id| id.0))
+        });
+        let mut visitor = JsonVisitor {
+            fields: serde_json::Map::new(),
+            redact: !LOG_SQL.load(Ordering::Relaxed),
+        };
+        event.record(&mut visitor);
+        let message = jsonrpc::notification(
+            "notifications/message",
+            serde_json::json!({
+                "level": level.as_str(),
+                "logger": event.metadata().target(),
+                "data": visitor.fields
+            }),
+        );
+
+        // Sessions whose client has gone away are dropped
+        sinks.retain(|id, sink| {
+            let addressed = match session {
+                Some(session) => session == *id,
+                None => sink.owner,
+            };
+            if level < sink.level || !addressed {
+                return true;
+            }
+            sink.outgoing
+                .upgrade()
+                .is_some_and(|outgoing| outgoing.send(message.clone()).is_ok())
+        });
+    }
+}
+
+struct SessionVisitor(Option<u64>);
+
+impl Visit for SessionVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "session" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Collects event fields into a JSON object, the message included.
+struct JsonVisitor {
+    fields: serde_json::Map<String, serde_json::Value>,
+    redact: bool,
+}
+
+impl JsonVisitor {
+    fn insert(&mut self, field: &Field, value: serde_json::Value) {
+        let value = if self.redact && REDACTED_FIELDS.contains(&field.name()) {
+            serde_json::Value::from("[redacted]")
+        } else {
+            value
+        };
+        self.fields.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for JsonVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value).into());
+    }
+}
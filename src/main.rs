@@ -1,13 +1,28 @@
+
+mod auth;
+mod confirm;
 mod db;
+mod http;
+mod jsonrpc;
+mod logging;
+mod prompts;
+mod resources;
+mod server;
+mod stdio;
 mod tools;
+#[cfg(unix)]
+mod unix;
 
-use anyhow::anyhow;
-use clap::Parser;
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use db::migrations::MigrationCommand;
 use db::SqliteDatabase;
+use tools::export_data_dictionary::DictionaryFormat;
 use std::sync::Arc;
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tracing::info;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 #[derive(Parser, Debug)]
 #[command(name = "sqlite-mcp-rs")]
@@ -20,481 +35,221 @@ struct Args {
     /// Read-only mode (default: false)
     #[arg(short, long, default_value = "false")]
     readonly: bool,
-}
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    /// Allow CRUD and DDL tools to access internal tables such as _table_comment (default: false)
+    #[arg(long, default_value = "false")]
+    admin: bool,
 
-    init_logging();
+    /// How clients connect to the server
+    #[arg(long, value_enum, default_value = "stdio")]
+    transport: Transport,
 
-    info!("Starting SQLite MCP Server...");
-    info!("Database path: {:?}", args.db_path);
-    info!("Read-only mode: {}", args.readonly);
+    /// Address the HTTP transport listens on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: std::net::SocketAddr,
 
-    let db: Arc<dyn db::DatabaseAdapter> = Arc::new(SqliteDatabase::new(&args.db_path, args.readonly)?);
+    /// Socket path the unix transport listens on
+    #[arg(long, required_if_eq("transport", "unix"))]
+    socket: Option<std::path::PathBuf>,
 
-    run_stdio_server(db).await?;
+    /// File of scope:token entries (read, write or admin) required as bearer
+    /// tokens by the HTTP transport; defaults to the SQLITE_MCP_TOKENS variable
+    #[arg(long)]
+    tokens_file: Option<std::path::PathBuf>,
 
-    Ok(())
-}
+    /// Rows an update may affect before it asks the user for confirmation, when
+    /// the client supports elicitation. Deletes always ask; above this many
+    /// rows the question includes a preview of the rows
+    #[arg(long, default_value = "10")]
+    confirm_threshold: usize,
 
-fn init_logging() {
-    let log_level = std::env::var("RUST_LOG")
-        .unwrap_or_else(|_| "sqlite_mcp=info,tokio=info".to_string());
-    
-    let filter = EnvFilter::new(&log_level);
+    /// Include SQL text and parameters in log messages sent to MCP clients (default: false)
+    #[arg(long, default_value = "false")]
+    log_sql: bool,
 
-    FmtSubscriber::builder()
-        .with_max_level(tracing::Level::INFO)
-        .with_env_filter(filter)
-        .with_writer(std::io::stderr)
-        .init();
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-async fn run_stdio_server(db: Arc<dyn db::DatabaseAdapter>) -> anyhow::Result<()> {
-    let mut reader = BufReader::new(io::stdin());
-    let mut writer = io::stdout();
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Transport {
+    /// Newline-delimited JSON-RPC on stdin/stdout
+    Stdio,
+    /// MCP Streamable HTTP on --listen
+    Http,
+    /// Newline-delimited JSON-RPC on the Unix domain socket --socket
+    Unix,
+}
 
-    let mut line = String::new();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run versioned schema migrations against the database and exit
+    Migrate {
+        #[arg(value_enum)]
+        action: MigrateAction,
+
+        /// Directory containing <version>_<name>.<up|down>.<sql|json> files
+        #[arg(long, default_value = "migrations")]
+        dir: std::path::PathBuf,
+
+        /// up: apply only up to this version; down: revert every migration above it
+        #[arg(long)]
+        target: Option<i64>,
+
+        /// Print the statements that would run without executing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compare the schema of the database with another database file and exit
+    SchemaDiff {
+        /// Database file with the desired schema
+        #[arg(long)]
+        target: std::path::PathBuf,
+
+        /// Database file to compare from (default: --db-path)
+        #[arg(long)]
+        source: Option<std::path::PathBuf>,
+
+        /// Print the migration SQL instead of the JSON diff
+        #[arg(long)]
+        sql: bool,
+    },
+    /// Print the data dictionary of the whole database and exit
+    DataDictionary {
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: DictionaryFormatArg,
+    },
+    /// Set table and column comments from a JSON or YAML document and exit
+    ImportComments {
+        /// Document of the form {table: {desc, columns: {column: desc}}}
+        file: std::path::PathBuf,
+    },
+}
 
-    loop {
-        line.clear();
-        let bytes_read = reader.read_line(&mut line).await?;
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DictionaryFormatArg {
+    Markdown,
+    Json,
+}
 
-        if bytes_read == 0 {
-            break;
+impl From<DictionaryFormatArg> for DictionaryFormat {
+    fn from(format: DictionaryFormatArg) -> Self {
+        match format {
+            DictionaryFormatArg::Markdown => DictionaryFormat::Markdown,
+            DictionaryFormatArg::Json => DictionaryFormat::Json,
         }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum MigrateAction {
+    Status,
+    Up,
+    Down,
+}
 
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+impl From<MigrateAction> for MigrationCommand {
+    fn from(action: MigrateAction) -> Self {
+        match action {
+            MigrateAction::Status => MigrationCommand::Status,
+            MigrateAction::Up => MigrationCommand::Up,
+            MigrateAction::Down => MigrationCommand::Down,
         }
+    }
+}
 
-        tracing::debug!("Received: {}", line);
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
 
-        let parsed: serde_json::Result<serde_json::Value> = serde_json::from_str(line);
-        let request_id = parsed.as_ref().ok().and_then(|v| v.get("id").cloned());
+    init_logging(args.log_sql);
 
-        match handle_message(&db, line).await {
-            Ok(response) => {
-                if let Some(resp) = response {
-                    let response_json = serde_json::to_string(&resp)?;
-                    tracing::debug!("Sending: {}", response_json);
-                    writer.write_all(response_json.as_bytes()).await?;
-                    writer.write_all(b"\n").await?;
-                    writer.flush().await?;
-                }
+    info!("Starting SQLite MCP Server...");
+    info!("Database path: {:?}", args.db_path);
+    info!("Read-only mode: {}", args.readonly);
+    info!("Admin mode: {}", args.admin);
+    info!("Transport: {:?}", args.transport);
+
+    let db: Arc<dyn db::DatabaseAdapter> =
+        Arc::new(SqliteDatabase::new(&args.db_path, args.readonly)?.with_admin(args.admin));
+
+    match args.command {
+        Some(command) => run_command(&db, command).await?,
+        None => match args.transport {
+            Transport::Stdio => stdio::serve(db, args.confirm_threshold).await?,
+            Transport::Http => {
+                let tokens = auth::TokenStore::load(args.tokens_file.as_deref())?;
+                http::serve(db, args.listen, tokens, args.confirm_threshold).await?
             }
-            Err(e) => {
-                tracing::error!("Error handling message: {}", e);
-                let error_response = serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": request_id.unwrap_or_else(|| serde_json::Value::Null),
-                    "error": {
-                        "code": -32603,
-                        "message": format!("Internal error: {}", e)
-                    }
-                });
-                let error_json = serde_json::to_string(&error_response)?;
-                writer.write_all(error_json.as_bytes()).await?;
-                writer.write_all(b"\n").await?;
-                writer.flush().await?;
+            #[cfg(unix)]
+            Transport::Unix => {
+                let socket = args.socket.context("--transport unix requires --socket")?;
+                unix::serve(db, &socket, args.confirm_threshold).await?
             }
-        }
+            #[cfg(not(unix))]
+            Transport::Unix => anyhow::bail!("The unix transport is only available on Unix"),
+        },
     }
 
     Ok(())
 }
 
-async fn handle_message(
-    db: &Arc<dyn db::DatabaseAdapter>,
-    message: &str,
-) -> anyhow::Result<Option<serde_json::Value>> {
-    let json: serde_json::Value = serde_json::from_str(message)?;
-
-    let method = json.get("method").and_then(|m| m.as_str());
-    let id = json.get("id");
-
-    match method {
-        Some("initialize") => handle_initialize(id).map(Some),
-        Some("initialized") => {
-            info!("Client initialized");
-            Ok(None)
+async fn run_command(db: &Arc<dyn db::DatabaseAdapter>, command: Command) -> anyhow::Result<()> {
+    let output = match command {
+        Command::Migrate {
+            action,
+            dir,
+            target,
+            dry_run,
+        } => {
+            let report = db.migrate(&dir, action.into(), target, dry_run).await?;
+            serde_json::to_string_pretty(&report)?
         }
-        Some("tools/list") => handle_tools_list(id).map(Some),
-        Some("tools/call") => handle_tools_call(db, json.clone(), id).await.map(Some),
-        Some("shutdown") => {
-            info!("Shutdown requested");
-            Ok(None)
+        Command::SchemaDiff {
+            target,
+            source,
+            sql,
+        } => {
+            let diff = db.schema_diff(source.as_deref(), &target, sql).await?;
+            if sql {
+                diff.migration_sql
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|stmt| format!("{};", stmt))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                serde_json::to_string_pretty(&diff)?
+            }
         }
-        Some(m) => {
-            tracing::warn!("Unknown method: {}", m);
-            Ok(None)
+        Command::DataDictionary { format } => {
+            tools::export_data_dictionary::export(db, format.into()).await?
         }
-        None => Ok(None),
-    }
-}
-
-fn handle_initialize(id: Option<&serde_json::Value>) -> anyhow::Result<serde_json::Value> {
-    Ok(serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": id,
-        "result": {
-            "protocolVersion": "2025-06-18",
-            "capabilities": {
-                "tools": {
-                    "listChanged": false
-                }
-            },
-            "serverInfo": {
-                "name": "sqlite-mcp-rs",
-                "version": env!("CARGO_PKG_VERSION")
-            }
+        Command::ImportComments { file } => {
+            let document = tools::import_comments::load_document(&file)?;
+            let result = db.import_comments(document).await?;
+            serde_json::to_string_pretty(&result)?
         }
-    }))
-}
+    };
 
-fn handle_tools_list(id: Option<&serde_json::Value>) -> anyhow::Result<serde_json::Value> {
-    Ok(serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": id,
-        "result": {
-            "tools": [
-                {
-                    "name": "list_tables",
-                    "title": "List Tables",
-                    "description": "List all tables in the database",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {}
-                    }
-                },
-                {
-                    "name": "get_table_schema",
-                    "title": "Get Table Schema",
-                    "description": "Get the schema of a specific table",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            }
-                        },
-                        "required": ["table"]
-                    }
-                },
-                {
-                    "name": "query_records",
-                    "title": "Query Records",
-                    "description": "Query records from a table with optional filters and ordering. Returns: records (array), total (count of all matching records before limit/offset), returned (count of records in this response)",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "filters": {
-                                "type": "object",
-                                "description": "Filter conditions. Multiple columns are combined with AND. Supported operators: $eq, $ne, $gt, $gte, $lt, $lte, $in, $like",
-                                "additionalProperties": {
-                                    "type": "object",
-                                    "properties": {
-                                        "$eq": {
-                                            "description": "Equals"
-                                        },
-                                        "$ne": {
-                                            "description": "Not equals"
-                                        },
-                                        "$gt": {
-                                            "description": "Greater than"
-                                        },
-                                        "$gte": {
-                                            "description": "Greater than or equal"
-                                        },
-                                        "$lt": {
-                                            "description": "Less than"
-                                        },
-                                        "$lte": {
-                                            "description": "Less than or equal"
-                                        },
-                                        "$in": {
-                                            "type": "array",
-                                            "description": "Value in list"
-                                        },
-                                        "$like": {
-                                            "type": "string",
-                                            "description": "Pattern matching (use % for wildcard)"
-                                        }
-                                    }
-                                }
-                            },
-                            "orders": {
-                                "type": "array",
-                                "description": "Ordering rules. Each rule can be either column-based or random. For column-based: use {\"column\": \"col_name\", \"direction\": \"asc|desc\"}. For random: use {\"random\": true}",
-                                "items": {
-                                    "type": "object",
-                                    "properties": {
-                                        "column": {
-                                            "type": "string",
-                                            "description": "Column name for ordering"
-                                        },
-                                        "direction": {
-                                            "type": "string",
-                                            "enum": ["asc", "desc"],
-                                            "description": "Sort direction (default: desc)"
-                                        },
-                                        "random": {
-                                            "type": "boolean",
-                                            "description": "Use random ordering (mutually exclusive with column/direction)"
-                                        }
-                                    }
-                                }
-                            },
-                            "limit": {
-                                "type": "integer",
-                                "description": "Maximum number of results (optional)"
-                            },
-                            "offset": {
-                                "type": "integer",
-                                "description": "Offset for pagination (optional)"
-                            }
-                        },
-                        "required": ["table"]
-                    }
-                },
-                {
-                    "name": "insert_record",
-                    "title": "Insert Record",
-                    "description": "Insert a new record into a table",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "data": {
-                                "type": "object",
-                                "description": "Record data as JSON object"
-                            }
-                        },
-                        "required": ["table", "data"]
-                    }
-                },
-                {
-                    "name": "update_record",
-                    "title": "Update Record",
-                    "description": "Update an existing record by ID",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "id": {
-                                "type": "integer",
-                                "description": "Record ID"
-                            },
-                            "data": {
-                                "type": "object",
-                                "description": "Updated data as JSON object"
-                            }
-                        },
-                        "required": ["table", "id", "data"]
-                    }
-                },
-                {
-                    "name": "delete_record",
-                    "title": "Delete Record",
-                    "description": "Delete a record by ID",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "id": {
-                                "type": "integer",
-                                "description": "Record ID"
-                            }
-                        },
-                        "required": ["table", "id"]
-                    }
-                },
-                {
-                    "name": "batch_insert",
-                    "title": "Batch Insert",
-                    "description": "Insert multiple records (max 100 items)",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "items": {
-                                "type": "array",
-                                "description": "Array of records to insert",
-                                "items": {
-                                    "type": "object"
-                                },
-                                "maxItems": 100
-                            },
-                            "batch_size": {
-                                "type": "integer",
-                                "description": "Number of records per transaction (default: 50)",
-                                "default": 50
-                            }
-                        },
-                        "required": ["table", "items"]
-                    }
-                },
-                {
-                    "name": "batch_update",
-                    "title": "Batch Update",
-                    "description": "Update multiple records (max 100 items)",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "updates": {
-                                "type": "array",
-                                "description": "Array of {id, data} objects",
-                                "items": {
-                                    "type": "object",
-                                    "properties": {
-                                        "id": {"type": "integer"},
-                                        "data": {"type": "object"}
-                                    },
-                                    "required": ["id", "data"]
-                                },
-                                "maxItems": 100
-                            },
-                            "batch_size": {
-                                "type": "integer",
-                                "description": "Number of records per transaction (default: 50)",
-                                "default": 50
-                            }
-                        },
-                        "required": ["table", "updates"]
-                    }
-                },
-                {
-                    "name": "batch_delete",
-                    "title": "Batch Delete",
-                    "description": "Delete multiple records by IDs",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "ids": {
-                                "type": "array",
-                                "description": "Array of record IDs to delete",
-                                "items": {
-                                    "type": "integer"
-                                }
-                            }
-                        },
-                        "required": ["table", "ids"]
-                    }
-                },
-                {
-                    "name": "set_table_comment",
-                    "title": "Set Table Comment",
-                    "description": "Set or update the description of a table",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "desc": {
-                                "type": "string",
-                                "description": "Table description"
-                            }
-                        },
-                        "required": ["table", "desc"]
-                    }
-                },
-                {
-                    "name": "set_column_comment",
-                    "title": "Set Column Comment",
-                    "description": "Set or update the description of a column",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "table": {
-                                "type": "string",
-                                "description": "Table name"
-                            },
-                            "column": {
-                                "type": "string",
-                                "description": "Column name"
-                            },
-                            "desc": {
-                                "type": "string",
-                                "description": "Column description"
-                            }
-                        },
-                        "required": ["table", "column", "desc"]
-                    }
-                }
-            ]
-        }
-    }))
+    println!("{}", output);
+    Ok(())
 }
 
-async fn handle_tools_call(
-    db: &Arc<dyn db::DatabaseAdapter>,
-    json: serde_json::Value,
-    id: Option<&serde_json::Value>,
-) -> anyhow::Result<serde_json::Value> {
-    let params = json.get("params");
-    let tool_name = params
-        .and_then(|p| p.get("name"))
-        .and_then(|n| n.as_str())
-        .ok_or_else(|| anyhow!("Missing tool name"))?;
-
-    let empty_args = serde_json::json!({});
-    let arguments = params.and_then(|p| p.get("arguments")).unwrap_or(&empty_args);
-
-    let result = match tool_name {
-        "list_tables" => tools::list_tables::execute(db, arguments).await?,
-        "get_table_schema" => tools::get_schema::execute(db, arguments).await?,
-        "query_records" => tools::query::execute(db, arguments).await?,
-        "insert_record" => tools::insert::execute(db, arguments).await?,
-        "update_record" => tools::update::execute(db, arguments).await?,
-        "delete_record" => tools::delete::execute(db, arguments).await?,
-        "batch_insert" => tools::batch::insert_execute(db, arguments).await?,
-        "batch_update" => tools::batch::update_execute(db, arguments).await?,
-        "batch_delete" => tools::batch::delete_execute(db, arguments).await?,
-        "set_table_comment" => tools::set_table_comment::execute(db, arguments).await?,
-        "set_column_comment" => tools::set_column_comment::execute(db, arguments).await?,
-        _ => return Err(anyhow!("Unknown tool: {}", tool_name)),
-    };
+fn init_logging(log_sql: bool) {
+    let log_level = std::env::var("RUST_LOG")
+        .unwrap_or_else(|_| "sqlite_mcp=info,tokio=info".to_string());
+    
+    let filter = EnvFilter::new(&log_level);
 
-    Ok(serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": id,
-        "result": {
-            "content": [
-                {
-                    "type": "text",
-                    "text": result
-                }
-            ]
-        }
-    }))
+    // RUST_LOG only filters stderr; clients choose their own level
+    logging::set_log_sql(log_sql);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(filter),
+        )
+        .with(logging::layer())
+        .init();
 }
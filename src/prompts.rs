@@ -0,0 +1,318 @@
+//! MCP prompts for common database workflows. Each prompt embeds the live
+//! schema and comments so the agent starts with the right context.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::db::adapter::TableSchema;
+use crate::db::DatabaseAdapter;
+use crate::jsonrpc::RpcError;
+
+/// Rows included as a sample in table prompts.
+const SAMPLE_ROWS: usize = 5;
+
+struct PromptArgument {
+    name: &'static str,
+    description: &'static str,
+    required: bool,
+}
+
+struct Prompt {
+    name: &'static str,
+    description: &'static str,
+    arguments: &'static [PromptArgument],
+}
+
+const TABLE_ARGUMENT: PromptArgument = PromptArgument {
+    name: "table",
+    description: "Name of the table",
+    required: true,
+};
+
+const PROMPTS: [Prompt; 4] = [
+    Prompt {
+        name: "explore_database",
+        description: "Explore this database: tables, row counts, relationships and comments",
+        arguments: &[],
+    },
+    Prompt {
+        name: "summarize_table",
+        description: "Summarize what a table stores, its keys and how it relates to other tables",
+        arguments: &[TABLE_ARGUMENT],
+    },
+    Prompt {
+        name: "safe_data_fix",
+        description: "Plan and apply a safe, verifiable data fix to a table",
+        arguments: &[
+            TABLE_ARGUMENT,
+            PromptArgument {
+                name: "problem",
+                description: "Description of the data problem to fix",
+                required: false,
+            },
+        ],
+    },
+    Prompt {
+        name: "document_columns",
+        description: "Write comments for tables and columns that have no description yet",
+        arguments: &[PromptArgument {
+            name: "table",
+            description: "Only document this table (defaults to all tables)",
+            required: false,
+        }],
+    },
+];
+
+/// Whether a schema entry still carries the default description (its own name).
+fn is_undocumented(name: &str, desc: &Option<String>) -> bool {
+    desc.as_deref().is_none_or(|d| d.trim().is_empty() || d == name)
+}
+
+fn description_suffix(name: &str, desc: &Option<String>) -> String {
+    match desc {
+        Some(d) if !is_undocumented(name, desc) => format!(" - {}", d),
+        _ => String::new(),
+    }
+}
+
+fn string_argument(arguments: &serde_json::Value, name: &str) -> Option<String> {
+    arguments
+        .get(name)
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .filter(|v| !v.is_empty())
+}
+
+fn required_argument(arguments: &serde_json::Value, prompt: &str, name: &str) -> anyhow::Result<String> {
+    string_argument(arguments, name)
+        .ok_or_else(|| {
+            RpcError::invalid_params(format!(
+                "Prompt '{}' requires argument '{}'",
+                prompt, name
+            ))
+            .into()
+        })
+}
+
+fn schema_json(schema: &TableSchema) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(schema)?)
+}
+
+async fn sample_rows(db: &Arc<dyn DatabaseAdapter>, table: &str) -> anyhow::Result<String> {
+    let rows = db.select(table, None, None, Some(SAMPLE_ROWS), None).await?;
+    Ok(serde_json::to_string_pretty(&rows)?)
+}
+
+async fn explore_database(db: &Arc<dyn DatabaseAdapter>) -> anyhow::Result<String> {
+    let schemas = db.list_schemas().await?;
+    let mut text = format!(
+        "Explore the SQLite database '{}' and give me an overview of what it contains.\n\n",
+        db.database_name().await
+    );
+
+    if schemas.is_empty() {
+        text.push_str("The database has no tables yet.\n");
+    }
+    for schema in &schemas {
+        let count = db.count(&schema.name, None).await?;
+        writeln!(
+            text,
+            "## {} ({} rows){}",
+            schema.name,
+            count,
+            description_suffix(&schema.name, &schema.desc)
+        )?;
+        for column in &schema.columns {
+            writeln!(
+                text,
+                "- {} {}{}{}",
+                column.name,
+                column.data_type,
+                if column.is_primary_key { " PRIMARY KEY" } else { "" },
+                description_suffix(&column.name, &column.desc)
+            )?;
+        }
+        for fk in &schema.foreign_keys {
+            writeln!(
+                text,
+                "- ({}) references {}({})",
+                fk.columns.join(", "),
+                fk.ref_table,
+                fk.ref_columns.join(", ")
+            )?;
+        }
+        text.push('\n');
+    }
+
+    text.push_str(
+        "Describe the purpose of each table, how the tables relate to each other, and which \
+         tables look most important. Use `query_records` with a small `limit` if you need to \
+         look at sample data, and point out anything that looks inconsistent or undocumented.",
+    );
+    Ok(text)
+}
+
+async fn summarize_table(db: &Arc<dyn DatabaseAdapter>, table: &str) -> anyhow::Result<String> {
+    let schema = db.get_schema(table).await?;
+    let count = db.count(table, None).await?;
+
+    Ok(format!(
+        "Summarize the table '{}' ({} rows).\n\n\
+         Schema, including table and column comments:\n```json\n{}\n```\n\n\
+         Sample rows:\n```json\n{}\n```\n\n\
+         Explain what a row represents, what each column means, which keys and indexes exist, \
+         and how the table relates to other tables through its foreign keys.",
+        table,
+        count,
+        schema_json(&schema)?,
+        sample_rows(db, table).await?
+    ))
+}
+
+async fn safe_data_fix(
+    db: &Arc<dyn DatabaseAdapter>,
+    table: &str,
+    problem: Option<String>,
+) -> anyhow::Result<String> {
+    let schema = db.get_schema(table).await?;
+    let problem = problem.unwrap_or_else(|| "(ask me to describe the problem first)".to_string());
+
+    Ok(format!(
+        "Write a safe data fix for the table '{}'.\n\n\
+         Problem: {}\n\n\
+         Schema, including table and column comments:\n```json\n{}\n```\n\n\
+         Follow these steps:\n\
+         1. Use `query_records` with filters to find exactly the rows that are affected, and \
+         show me how many there are.\n\
+         2. Show a few of the affected rows and the values they will have after the fix.\n\
+         3. Wait for my confirmation before changing anything.\n\
+         4. Apply the fix by primary key with `update_record` or `batch_update`, never by \
+         deleting and re-inserting rows. Respect `allowed_values`, NOT NULL constraints and \
+         foreign keys from the schema above.\n\
+         5. Query the rows again and verify the fix, then report what changed.",
+        table,
+        problem,
+        schema_json(&schema)?
+    ))
+}
+
+async fn document_columns(
+    db: &Arc<dyn DatabaseAdapter>,
+    table: Option<String>,
+) -> anyhow::Result<String> {
+    let schemas = match table {
+        Some(table) => vec![db.get_schema(&table).await?],
+        None => db.list_schemas().await?,
+    };
+
+    let mut undocumented = String::new();
+    for schema in &schemas {
+        let columns: Vec<&str> = schema
+            .columns
+            .iter()
+            .filter(|c| is_undocumented(&c.name, &c.desc))
+            .map(|c| c.name.as_str())
+            .collect();
+        let table_undocumented = is_undocumented(&schema.name, &schema.desc);
+        if !table_undocumented && columns.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            undocumented,
+            "## {}{}\n```json\n{}\n```\nSample rows:\n```json\n{}\n```\nUndocumented columns: {}\n",
+            schema.name,
+            if table_undocumented { " (table has no description)" } else { "" },
+            schema_json(schema)?,
+            sample_rows(db, &schema.name).await?,
+            if columns.is_empty() { "none".to_string() } else { columns.join(", ") }
+        )?;
+    }
+
+    if undocumented.is_empty() {
+        return Ok("Every table and column already has a description. \
+                   Review the existing comments with `get_table_schema` and suggest improvements."
+            .to_string());
+    }
+
+    Ok(format!(
+        "Document the tables and columns below that have no description yet. \
+         Infer their meaning from names, types, foreign keys and the sample rows, \
+         and keep existing descriptions unchanged.\n\n{}\
+         Propose a short description for each, then save them with `set_table_comment` and \
+         `set_column_comment`, or all at once with `import_comments`.",
+        undocumented
+    ))
+}
+
+pub fn handle_prompts_list() -> anyhow::Result<serde_json::Value> {
+    let prompts: Vec<serde_json::Value> = PROMPTS
+        .iter()
+        .map(|prompt| {
+            let arguments: Vec<serde_json::Value> = prompt
+                .arguments
+                .iter()
+                .map(|arg| {
+                    serde_json::json!({
+                        "name": arg.name,
+                        "description": arg.description,
+                        "required": arg.required
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "name": prompt.name,
+                "description": prompt.description,
+                "arguments": arguments
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({
+        "prompts": prompts
+    }))
+}
+
+pub async fn handle_prompts_get(
+    db: &Arc<dyn DatabaseAdapter>,
+    params: &serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let name = params
+        .get("name")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| RpcError::invalid_params("Missing prompt name"))?;
+
+    let default_args = serde_json::json!({});
+    let arguments = params.get("arguments").unwrap_or(&default_args);
+
+    let prompt = PROMPTS
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| RpcError::invalid_params(format!("Prompt not found: {}", name)))?;
+
+    let text = match name {
+        "explore_database" => explore_database(db).await?,
+        "summarize_table" => {
+            summarize_table(db, &required_argument(arguments, name, "table")?).await?
+        }
+        "safe_data_fix" => {
+            let table = required_argument(arguments, name, "table")?;
+            safe_data_fix(db, &table, string_argument(arguments, "problem")).await?
+        }
+        "document_columns" => document_columns(db, string_argument(arguments, "table")).await?,
+        _ => return Err(RpcError::invalid_params(format!("Prompt not found: {}", name)).into()),
+    };
+
+    Ok(serde_json::json!({
+        "description": prompt.description,
+        "messages": [
+            {
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": text
+                }
+            }
+        ]
+    }))
+}
@@ -0,0 +1,320 @@
+//! MCP resources: the schema and rows of every table, addressed as
+//! `sqlite://{db}/tables/{table}/schema` and
+//! `sqlite://{db}/tables/{table}/rows?limit=50`.
+
+use anyhow::anyhow;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+use tracing::debug;
+
+use crate::db::adapter::DataChange;
+use crate::db::error::error_code;
+use crate::db::DatabaseAdapter;
+use crate::jsonrpc::{self, RpcError};
+
+/// Comment tables whose rows show up in the schema resources.
+const COMMENT_TABLES: [&str; 2] = ["_table_comment", "_table_column_comment"];
+
+const DEFAULT_ROW_LIMIT: usize = 50;
+const MAX_ROW_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableResource {
+    Schema,
+    Rows { limit: usize, offset: Option<usize> },
+}
+
+/// Percent-encode a URI path segment.
+fn encode(segment: &str) -> String {
+    let mut out = String::new();
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+fn decode(segment: &str) -> anyhow::Result<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment
+                .get(i + 1..i + 3)
+                .ok_or_else(|| RpcError::invalid_params(format!("Invalid escape in '{}'", segment)))?;
+            out.push(
+                u8::from_str_radix(hex, 16)
+                    .map_err(|_| RpcError::invalid_params(format!("Invalid escape in '{}'", segment)))?,
+            );
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out)
+        .map_err(|_| RpcError::invalid_params(format!("Invalid UTF-8 in '{}'", segment)).into())
+}
+
+pub fn table_uri(db_name: &str, table: &str) -> String {
+    format!("sqlite://{}/tables/{}", encode(db_name), encode(table))
+}
+
+pub fn schema_uri(db_name: &str, table: &str) -> String {
+    format!("{}/schema", table_uri(db_name, table))
+}
+
+pub fn rows_uri(db_name: &str, table: &str) -> String {
+    format!("{}/rows?limit={}", table_uri(db_name, table), DEFAULT_ROW_LIMIT)
+}
+
+/// Split a resource URI into its table name and resource kind.
+pub fn parse_uri(db_name: &str, uri: &str) -> anyhow::Result<(String, TableResource)> {
+    let not_found = || RpcError::resource_not_found(uri);
+
+    let rest = uri.strip_prefix("sqlite://").ok_or_else(not_found)?;
+    let (path, query) = match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (rest, None),
+    };
+
+    let segments: Vec<&str> = path.split('/').collect();
+    let [db, "tables", table, kind] = segments.as_slice() else {
+        return Err(not_found().into());
+    };
+    if decode(db)? != db_name {
+        return Err(not_found().into());
+    }
+    let table = decode(table)?;
+
+    let resource = match *kind {
+        "schema" => TableResource::Schema,
+        "rows" => {
+            let mut limit = DEFAULT_ROW_LIMIT;
+            let mut offset = None;
+            for pair in query.unwrap_or_default().split('&').filter(|p| !p.is_empty()) {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                let value: usize = value
+                    .parse()
+                    .map_err(|_| {
+                        RpcError::invalid_params(format!("Invalid value for '{}' in {}", key, uri))
+                    })?;
+                match key {
+                    "limit" => limit = value.min(MAX_ROW_LIMIT),
+                    "offset" => offset = Some(value),
+                    _ => {
+                        return Err(RpcError::invalid_params(format!(
+                            "Unknown query parameter '{}' in {}",
+                            key, uri
+                        ))
+                        .into())
+                    }
+                }
+            }
+            TableResource::Rows { limit, offset }
+        }
+        _ => return Err(not_found().into()),
+    };
+
+    Ok((table, resource))
+}
+
+pub async fn handle_resources_list(
+    db: &Arc<dyn DatabaseAdapter>,
+) -> anyhow::Result<serde_json::Value> {
+    let db_name = db.database_name().await;
+    let mut resources = Vec::new();
+
+    for schema in db.list_schemas().await? {
+        let desc = schema.desc.clone().unwrap_or_else(|| schema.name.clone());
+        resources.push(serde_json::json!({
+            "uri": schema_uri(&db_name, &schema.name),
+            "name": format!("{} schema", schema.name),
+            "description": format!("Columns, keys, indexes and comments of table '{}': {}", schema.name, desc),
+            "mimeType": "application/json"
+        }));
+        resources.push(serde_json::json!({
+            "uri": rows_uri(&db_name, &schema.name),
+            "name": format!("{} rows", schema.name),
+            "description": format!("First {} rows of table '{}'", DEFAULT_ROW_LIMIT, schema.name),
+            "mimeType": "application/json"
+        }));
+    }
+
+    Ok(serde_json::json!({
+        "resources": resources
+    }))
+}
+
+pub async fn handle_resource_templates_list(
+    db: &Arc<dyn DatabaseAdapter>,
+) -> anyhow::Result<serde_json::Value> {
+    let db_name = encode(&db.database_name().await);
+
+    Ok(serde_json::json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": format!("sqlite://{}/tables/{{table}}/schema", db_name),
+                "name": "Table schema",
+                "description": "Columns, keys, indexes and comments of a table",
+                "mimeType": "application/json"
+            },
+            {
+                "uriTemplate": format!("sqlite://{}/tables/{{table}}/rows{{?limit,offset}}", db_name),
+                "name": "Table rows",
+                "description": format!("Rows of a table (limit defaults to {}, at most {})", DEFAULT_ROW_LIMIT, MAX_ROW_LIMIT),
+                "mimeType": "application/json"
+            }
+        ]
+    }))
+}
+
+pub async fn handle_resources_read(
+    db: &Arc<dyn DatabaseAdapter>,
+    params: &serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let uri = uri_param(params)?;
+
+    let (table, resource) = parse_uri(&db.database_name().await, uri)?;
+    let not_found = |e: anyhow::Error| match error_code(&e) {
+        "unknown_table" => RpcError::resource_not_found(uri).into(),
+        _ => e,
+    };
+    let text = match resource {
+        TableResource::Schema => {
+            serde_json::to_string_pretty(&db.get_schema(&table).await.map_err(not_found)?)?
+        }
+        TableResource::Rows { limit, offset } => {
+            let records = db
+                .select(&table, None, None, Some(limit), offset)
+                .await
+                .map_err(not_found)?;
+            serde_json::to_string_pretty(&records)?
+        }
+    };
+
+    Ok(serde_json::json!({
+        "contents": [
+            {
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": text
+            }
+        ]
+    }))
+}
+
+/// Resource URIs the client has subscribed to.
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+    uris: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl Subscriptions {
+    fn insert(&self, uri: &str) -> anyhow::Result<()> {
+        self.uris
+            .lock()
+            .map_err(|e| anyhow!("Failed to lock subscriptions: {}", e))?
+            .insert(uri.to_string());
+        Ok(())
+    }
+
+    fn remove(&self, uri: &str) -> anyhow::Result<()> {
+        self.uris
+            .lock()
+            .map_err(|e| anyhow!("Failed to lock subscriptions: {}", e))?
+            .remove(uri);
+        Ok(())
+    }
+
+    /// Subscribed URIs whose content may have been affected by `change`.
+    fn affected(&self, db_name: &str, change: &DataChange) -> Vec<String> {
+        let Ok(uris) = self.uris.lock() else {
+            return Vec::new();
+        };
+
+        uris.iter()
+            .filter(|uri| {
+                let Ok((table, resource)) = parse_uri(db_name, uri) else {
+                    return false;
+                };
+                match change {
+                    DataChange::External => true,
+                    DataChange::Tables(tables) => {
+                        tables.contains(&table)
+                            || (resource == TableResource::Schema
+                                && tables.iter().any(|t| COMMENT_TABLES.contains(&t.as_str())))
+                    }
+                }
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn uri_param(params: &serde_json::Value) -> anyhow::Result<&str> {
+    params
+        .get("uri")
+        .and_then(|u| u.as_str())
+        .ok_or_else(|| RpcError::invalid_params("Missing resource uri").into())
+}
+
+pub async fn handle_resources_subscribe(
+    db: &Arc<dyn DatabaseAdapter>,
+    subscriptions: &Subscriptions,
+    params: &serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let uri = uri_param(params)?;
+    parse_uri(&db.database_name().await, uri)?;
+    subscriptions.insert(uri)?;
+    debug!("Subscribed to {}", uri);
+
+    Ok(serde_json::json!({}))
+}
+
+pub async fn handle_resources_unsubscribe(
+    subscriptions: &Subscriptions,
+    params: &serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let uri = uri_param(params)?;
+    subscriptions.remove(uri)?;
+    debug!("Unsubscribed from {}", uri);
+
+    Ok(serde_json::json!({}))
+}
+
+/// Turn committed changes into `notifications/resources/updated` for the
+/// subscribed URIs they affect. Runs until the outgoing channel closes.
+pub async fn forward_changes(
+    db: Arc<dyn DatabaseAdapter>,
+    subscriptions: Subscriptions,
+    outgoing: mpsc::UnboundedSender<serde_json::Value>,
+) {
+    let db_name = db.database_name().await;
+    let mut changes = db.subscribe_changes().await;
+
+    loop {
+        let change = match changes.recv().await {
+            Ok(change) => change,
+            // Missed some changes; assume everything changed
+            Err(broadcast::error::RecvError::Lagged(_)) => DataChange::External,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        for uri in subscriptions.affected(&db_name, &change) {
+            debug!("Resource updated: {}", uri);
+            let notification = jsonrpc::notification(
+                "notifications/resources/updated",
+                serde_json::json!({ "uri": uri }),
+            );
+            if outgoing.send(notification).is_err() {
+                return;
+            }
+        }
+    }
+}
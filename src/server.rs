@@ -0,0 +1,592 @@
+//! Transport-independent MCP dispatcher: validates JSON-RPC 2.0 messages and
+//! batches, routes requests to the method handlers and builds the responses.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, Instrument};
+
+use crate::auth::Scope;
+use crate::db::cancel::{self, CancelToken};
+use crate::db::progress::{self, ProgressSink};
+use crate::db::{self, DatabaseAdapter};
+use crate::jsonrpc::{self, Request, RequestId, Response, RpcError};
+use crate::logging::{self, LoggingLevel};
+use crate::{confirm, prompts, resources, tools};
+
+/// Protocol versions the server can speak, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// How long a request to the client, e.g. a confirmation the user has to
+/// answer, may stay unanswered.
+const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Requests sent to the client, waiting for its response.
+type PendingRequests =
+    Mutex<HashMap<RequestId, oneshot::Sender<Result<serde_json::Value, RpcError>>>>;
+
+#[derive(Clone)]
+pub struct Server {
+    db: Arc<dyn DatabaseAdapter>,
+    subscriptions: resources::Subscriptions,
+    /// Server-to-client notifications, e.g. progress
+    outgoing: mpsc::UnboundedSender<serde_json::Value>,
+    /// Requests being handled, so `notifications/cancelled` can find them
+    in_flight: Arc<Mutex<HashMap<RequestId, CancelToken>>>,
+    /// What the client may do; unauthenticated transports get everything
+    scope: Scope,
+    /// Routes events logged while handling this client's requests back to it
+    session: u64,
+    /// Whether the client also gets events logged outside any session
+    owner: bool,
+    pending: Arc<PendingRequests>,
+    next_request_id: Arc<AtomicI64>,
+    /// Whether the client declared the elicitation capability
+    elicitation: Arc<AtomicBool>,
+    /// Updates affecting more rows than this need the user's confirmation
+    confirm_threshold: usize,
+}
+
+impl Server {
+    pub fn new(
+        db: Arc<dyn DatabaseAdapter>,
+        subscriptions: resources::Subscriptions,
+        outgoing: mpsc::UnboundedSender<serde_json::Value>,
+    ) -> Self {
+        Self {
+            db,
+            subscriptions,
+            outgoing,
+            in_flight: Arc::default(),
+            scope: Scope::Admin,
+            session: logging::new_session(),
+            owner: false,
+            pending: Arc::default(),
+            next_request_id: Arc::new(AtomicI64::new(1)),
+            elicitation: Arc::default(),
+            confirm_threshold: usize::MAX,
+        }
+    }
+
+    /// Restrict the tools the client may list and call.
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Whether to send the client events logged outside any session too,
+    /// e.g. at startup. Meant for a transport serving a single client.
+    pub fn with_transport_owner(mut self, owner: bool) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// Ask before deleting rows, and before updating more than `threshold`
+    /// rows, when the client supports elicitation.
+    pub fn with_confirm_threshold(mut self, threshold: usize) -> Self {
+        self.confirm_threshold = threshold;
+        self
+    }
+
+    /// Span tying events logged outside a request, e.g. by the transport,
+    /// to this client.
+    pub fn span(&self) -> tracing::Span {
+        tracing::debug_span!("session", session = self.session)
+    }
+
+    /// Handle one incoming payload, either a single message or a batch.
+    /// Returns the payload to send back; `None` when there is nothing to send,
+    /// e.g. for notifications.
+    pub async fn handle_message(&self, message: &str) -> Option<serde_json::Value> {
+        match serde_json::from_str(message) {
+            Ok(value) => self.handle_json(value).await,
+            Err(e) => Some(to_json(Response::error(None, RpcError::parse_error(e)))),
+        }
+    }
+
+    /// Like `handle_message`, for a payload that has already been parsed.
+    pub async fn handle_json(&self, value: serde_json::Value) -> Option<serde_json::Value> {
+        match value {
+            serde_json::Value::Array(batch) => {
+                if batch.is_empty() {
+                    let error = RpcError::invalid_request("empty batch");
+                    return Some(to_json(Response::error(None, error)));
+                }
+                let mut responses = Vec::new();
+                for message in batch {
+                    if let Some(response) = self.handle_value(message).await {
+                        responses.push(response);
+                    }
+                }
+                // A batch of notifications gets no response at all
+                if responses.is_empty() {
+                    None
+                } else {
+                    Some(to_json(responses))
+                }
+            }
+            value => self.handle_value(value).await.map(to_json),
+        }
+    }
+
+    async fn handle_value(&self, value: serde_json::Value) -> Option<Response> {
+        // Responses to requests the server sent, e.g. elicitation
+        if value.get("method").is_none()
+            && (value.get("result").is_some() || value.get("error").is_some())
+        {
+            self.handle_client_response(value);
+            return None;
+        }
+
+        match Request::from_value(value) {
+            Ok(request) => self.handle_request(request).await,
+            Err(response) => Some(response),
+        }
+    }
+
+    fn handle_client_response(&self, value: serde_json::Value) {
+        let waiter = value
+            .get("id")
+            .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok())
+            .and_then(|id| self.pending.lock().ok()?.remove(&id));
+        let Some(waiter) = waiter else {
+            tracing::debug!("Ignoring response to unknown request: {}", value);
+            return;
+        };
+
+        let result = match value.get("error") {
+            Some(error) => Err(RpcError {
+                code: error
+                    .get("code")
+                    .and_then(|c| c.as_i64())
+                    .unwrap_or(RpcError::INTERNAL_ERROR),
+                message: error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            None => Ok(value.get("result").cloned().unwrap_or_default()),
+        };
+        let _ = waiter.send(result);
+    }
+
+    /// Send a request to the client and wait for its result.
+    async fn request_client(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let id = RequestId::Number(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        let (waiter, response) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id.clone(), waiter);
+        }
+        let _ = self.outgoing.send(jsonrpc::request(&id, method, params));
+
+        let response = tokio::time::timeout(CLIENT_REQUEST_TIMEOUT, response).await;
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+        match response {
+            Ok(Ok(result)) => Ok(result?),
+            Ok(Err(_)) => Err(anyhow::anyhow!("Client went away before answering {}", method)),
+            Err(_) => Err(anyhow::anyhow!("Client did not answer {} in time", method)),
+        }
+    }
+
+    /// Whether a tool call may go ahead: true unless it is a destructive
+    /// change the user was asked about and did not accept.
+    async fn confirm_change(
+        &self,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+    ) -> anyhow::Result<bool> {
+        if !self.elicitation.load(Ordering::Relaxed) {
+            return Ok(true);
+        }
+        let change =
+            confirm::pending_change(&self.db, tool_name, arguments, self.confirm_threshold).await?;
+        let Some(change) = change else {
+            return Ok(true);
+        };
+
+        let params = serde_json::json!({
+            "message": change.message(),
+            "requestedSchema": confirm::requested_schema()
+        });
+        match self.request_client("elicitation/create", params).await {
+            Ok(response) => Ok(confirm::accepted(&response)),
+            Err(e) => {
+                tracing::warn!("Confirmation of '{}' failed: {:#}", tool_name, e);
+                Ok(false)
+            }
+        }
+    }
+
+    pub async fn handle_request(&self, request: Request) -> Option<Response> {
+        let span = tracing::debug_span!(
+            "request",
+            session = self.session,
+            method = %request.method
+        );
+        let Some(id) = request.id.clone() else {
+            // Notifications never get a response, not even an error
+            if let Err(e) = self.dispatch(&request).instrument(span).await {
+                tracing::debug!("Notification '{}' failed: {:#}", request.method, e);
+            }
+            return None;
+        };
+
+        let token = CancelToken::default();
+        self.track(&id, Some(token.clone()));
+        let dispatch = cancel::CURRENT.scope(
+            token.clone(),
+            self.dispatch(&request).instrument(span),
+        );
+        let result = match self.progress_sink(&request) {
+            Some(sink) => progress::CURRENT.scope(sink, dispatch).await,
+            None => dispatch.await,
+        };
+        self.track(&id, None);
+
+        // The client has stopped waiting; a cancelled request gets no response
+        if token.is_cancelled() {
+            info!("Request {} ({}) cancelled", id, request.method);
+            return None;
+        }
+
+        Some(match result {
+            Ok(result) => Response::success(Some(id), result),
+            Err(e) => Response::error(Some(id), into_rpc_error(e)),
+        })
+    }
+
+    /// Sends `notifications/progress` for requests that carry a
+    /// `_meta.progressToken`.
+    fn progress_sink(&self, request: &Request) -> Option<ProgressSink> {
+        let token = request.params.get("_meta")?.get("progressToken")?.clone();
+        let outgoing = self.outgoing.clone();
+        Some(ProgressSink::new(move |processed, total, message| {
+            let _ = outgoing.send(jsonrpc::notification(
+                "notifications/progress",
+                serde_json::json!({
+                    "progressToken": token,
+                    "progress": processed,
+                    "total": total,
+                    "message": message
+                }),
+            ));
+        }))
+    }
+
+    fn track(&self, id: &RequestId, token: Option<CancelToken>) {
+        let Ok(mut in_flight) = self.in_flight.lock() else {
+            return;
+        };
+        match token {
+            Some(token) => in_flight.insert(id.clone(), token),
+            None => in_flight.remove(id),
+        };
+    }
+
+    fn handle_cancelled(&self, params: &serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        let id: RequestId = params
+            .get("requestId")
+            .and_then(|id| serde_json::from_value(id.clone()).ok())
+            .ok_or_else(|| RpcError::invalid_params("Missing requestId"))?;
+        let reason = params.get("reason").and_then(|r| r.as_str()).unwrap_or("no reason given");
+
+        let token = self
+            .in_flight
+            .lock()
+            .ok()
+            .and_then(|in_flight| in_flight.get(&id).cloned());
+        match token {
+            Some(token) => {
+                info!("Cancelling request {}: {}", id, reason);
+                token.cancel();
+            }
+            // Already finished, or never seen; either way there is nothing to stop
+            None => tracing::debug!("Cancellation for unknown request {}", id),
+        }
+        Ok(serde_json::Value::Null)
+    }
+
+    fn handle_set_level(&self, params: &serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        let level = params
+            .get("level")
+            .ok_or_else(|| RpcError::invalid_params("Missing level"))?;
+        let level: LoggingLevel = serde_json::from_value(level.clone())
+            .map_err(|_| RpcError::invalid_params(format!("Unknown log level: {}", level)))?;
+
+        logging::set_level(self.session, level, self.owner, &self.outgoing);
+        info!("Client log level set to {}", level.as_str());
+        Ok(serde_json::json!({}))
+    }
+
+    async fn handle_tools_call(
+        &self,
+        params: &serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let scope = self.scope;
+        let tool_name = params
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| RpcError::invalid_params("Missing tool name"))?;
+
+        let tool = tools::TOOLS
+            .iter()
+            .find(|tool| tool.name == tool_name)
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown tool: {}", tool_name)))?;
+
+        let empty_args = serde_json::json!({});
+        let arguments = params.get("arguments").unwrap_or(&empty_args);
+
+        let required = tool.required_scope(arguments);
+        if required > scope {
+            tracing::warn!("Tool '{}' denied for {} scope", tool_name, scope.as_str());
+            let message = if required > tool.scope {
+                format!(
+                    "Reading server files with '{}' requires the {} scope; this client has {}",
+                    tool_name,
+                    required.as_str(),
+                    scope.as_str()
+                )
+            } else {
+                format!(
+                    "Tool '{}' requires the {} scope; this client has {}",
+                    tool_name,
+                    required.as_str(),
+                    scope.as_str()
+                )
+            };
+            return error_result("forbidden", message);
+        }
+
+        let output = match self.confirm_change(tool_name, arguments).await {
+            Ok(true) => call_tool(&self.db, tool_name, arguments).await,
+            Ok(false) => {
+                info!("Tool '{}' not confirmed by the user", tool_name);
+                return error_result(
+                    "declined",
+                    format!("The user did not confirm the change made by '{}'", tool_name),
+                );
+            }
+            Err(e) => Err(e),
+        };
+
+        let tool_result = match output {
+            Ok(output) => {
+                let mut tool_result = serde_json::json!({
+                    "content": [
+                        {
+                            "type": "text",
+                            "text": output.text
+                        }
+                    ]
+                });
+                if let Some(structured) = output.structured {
+                    tool_result["structuredContent"] = structured;
+                }
+                tool_result
+            }
+            Err(e) if e.is::<tools::InvalidArguments>() => {
+                return Err(RpcError::invalid_params(e.to_string()).into());
+            }
+            Err(e) if e.is::<RpcError>() => return Err(e),
+            // Not a tool failure; the dispatcher drops the response
+            Err(e) if db::error::error_code(&e) == "cancelled" => return Err(e),
+            Err(e) => {
+                // Tool failures go back to the model as a result it can act on
+                let code = db::error::error_code(&e);
+                tracing::warn!("Tool '{}' failed ({}): {:#}", tool_name, code, e);
+                error_result(code, format!("{:#}", e))?
+            }
+        };
+
+        Ok(tool_result)
+    }
+
+    async fn dispatch(&self, request: &Request) -> anyhow::Result<serde_json::Value> {
+        let db = &self.db;
+        let params = &request.params;
+
+        match request.method.as_str() {
+            "initialize" => {
+                let capabilities = params.get("capabilities");
+                let elicitation = capabilities.and_then(|c| c.get("elicitation")).is_some();
+                self.elicitation.store(elicitation, Ordering::Relaxed);
+                handle_initialize(params)
+            }
+            "notifications/initialized" | "initialized" => {
+                info!("Client initialized");
+                Ok(serde_json::Value::Null)
+            }
+            "notifications/cancelled" => self.handle_cancelled(params),
+            "ping" => Ok(serde_json::json!({})),
+            "logging/setLevel" => self.handle_set_level(params),
+            "tools/list" => handle_tools_list(self.scope, db.is_readonly().await),
+            "tools/call" => self.handle_tools_call(params).await,
+            "prompts/list" => prompts::handle_prompts_list(),
+            "prompts/get" => prompts::handle_prompts_get(db, params).await,
+            "resources/list" => resources::handle_resources_list(db).await,
+            "resources/templates/list" => resources::handle_resource_templates_list(db).await,
+            "resources/read" => resources::handle_resources_read(db, params).await,
+            "resources/subscribe" => {
+                resources::handle_resources_subscribe(db, &self.subscriptions, params).await
+            }
+            "resources/unsubscribe" => {
+                resources::handle_resources_unsubscribe(&self.subscriptions, params).await
+            }
+            "shutdown" => {
+                info!("Shutdown requested");
+                Ok(serde_json::json!({}))
+            }
+            method => {
+                if !request.is_notification() {
+                    tracing::warn!("Unknown method: {}", method);
+                }
+                Err(RpcError::method_not_found(method).into())
+            }
+        }
+    }
+}
+
+/// Whether `value` only answers a request the server sent or cancels one of
+/// the client's. Such messages never touch the database, and running
+/// requests may be waiting for them, so transports should not queue them
+/// behind other requests.
+pub fn is_control_message(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Array(batch) => {
+            !batch.is_empty() && batch.iter().all(is_control_message)
+        }
+        value => match value.get("method").and_then(|m| m.as_str()) {
+            Some(method) => method == "notifications/cancelled",
+            None => value.get("result").is_some() || value.get("error").is_some(),
+        },
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+/// Protocol errors keep their code; anything else is an internal error.
+fn into_rpc_error(e: anyhow::Error) -> RpcError {
+    match e.downcast::<RpcError>() {
+        Ok(rpc) => rpc,
+        Err(e) => {
+            tracing::error!("Error handling message: {:#}", e);
+            RpcError {
+                code: RpcError::INTERNAL_ERROR,
+                message: format!("Internal error: {:#}", e),
+            }
+        }
+    }
+}
+
+/// Answer with the client's protocol version if we support it, otherwise
+/// with our latest one and let the client decide whether to continue.
+fn handle_initialize(params: &serde_json::Value) -> anyhow::Result<serde_json::Value> {
+    let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+    let protocol_version = requested
+        .and_then(|v| SUPPORTED_PROTOCOL_VERSIONS.iter().find(|s| **s == v))
+        .copied()
+        .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+    info!(
+        "Client requested protocol version {:?}, using {}",
+        requested, protocol_version
+    );
+
+    Ok(serde_json::json!({
+        "protocolVersion": protocol_version,
+        "capabilities": {
+            "tools": {
+                "listChanged": false
+            },
+            "prompts": {
+                "listChanged": false
+            },
+            "resources": {
+                "subscribe": true,
+                "listChanged": false
+            },
+            "logging": {}
+        },
+        "serverInfo": {
+            "name": "sqlite-mcp-rs",
+            "version": env!("CARGO_PKG_VERSION")
+        }
+    }))
+}
+
+/// Tools the client may call. A read-only database hides every tool that
+/// would modify it.
+fn handle_tools_list(scope: Scope, readonly: bool) -> anyhow::Result<serde_json::Value> {
+    let tools: Vec<serde_json::Value> = tools::TOOLS
+        .iter()
+        .filter(|tool| tool.scope <= scope)
+        .filter(|tool| tool.annotations.read_only || !readonly)
+        .map(|tool| tool.definition())
+        .collect();
+
+    Ok(serde_json::json!({
+        "tools": tools
+    }))
+}
+
+/// Tool result reporting a failure the model can act on.
+fn error_result(code: &str, message: String) -> anyhow::Result<serde_json::Value> {
+    let error = serde_json::json!({
+        "error": {
+            "code": code,
+            "message": message
+        }
+    });
+    Ok(serde_json::json!({
+        "content": [
+            {
+                "type": "text",
+                "text": serde_json::to_string_pretty(&error)?
+            }
+        ],
+        "isError": true
+    }))
+}
+
+async fn call_tool(
+    db: &Arc<dyn db::DatabaseAdapter>,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+) -> anyhow::Result<tools::ToolOutput> {
+    match tool_name {
+        "list_tables" => tools::list_tables::execute(db, arguments).await,
+        "get_table_schema" => tools::get_schema::execute(db, arguments).await,
+        "query_records" => tools::query::execute(db, arguments).await,
+        "insert_record" => tools::insert::execute(db, arguments).await,
+        "update_record" => tools::update::execute(db, arguments).await,
+        "delete_record" => tools::delete::execute(db, arguments).await,
+        "batch_insert" => tools::batch::insert_execute(db, arguments).await,
+        "batch_update" => tools::batch::update_execute(db, arguments).await,
+        "batch_delete" => tools::batch::delete_execute(db, arguments).await,
+        "set_table_comment" => tools::set_table_comment::execute(db, arguments).await,
+        "set_column_comment" => tools::set_column_comment::execute(db, arguments).await,
+        "set_column_metadata" => tools::set_column_metadata::execute(db, arguments).await,
+        "create_table" => tools::ddl::create_table_execute(db, arguments).await,
+        "add_column" => tools::ddl::add_column_execute(db, arguments).await,
+        "create_index" => tools::ddl::create_index_execute(db, arguments).await,
+        "drop_index" => tools::ddl::drop_index_execute(db, arguments).await,
+        "alter_table" => tools::alter_table::execute(db, arguments).await,
+        "migrate" => tools::migrate::execute(db, arguments).await,
+        "schema_diff" => tools::schema_diff::execute(db, arguments).await,
+        "export_schema_diagram" => tools::export_schema_diagram::execute(db, arguments).await,
+        "export_data_dictionary" => tools::export_data_dictionary::execute(db, arguments).await,
+        "import_comments" => tools::import_comments::execute(db, arguments).await,
+        _ => Err(RpcError::invalid_params(format!("Unknown tool: {}", tool_name)).into()),
+    }
+}
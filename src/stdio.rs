@@ -0,0 +1,141 @@
+//! Newline-delimited JSON-RPC over a byte stream: stdin/stdout, or one
+//! connection of the Unix socket transport.
+
+use std::sync::Arc;
+
+use tokio::io::{self, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use crate::db::DatabaseAdapter;
+use crate::resources;
+use crate::server::{self, Server};
+
+/// Requests handled at the same time. Further requests wait in the reader
+/// until a handler frees up.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+/// Serve the client on stdin/stdout. As the only client, it also gets the
+/// server's own log events.
+pub async fn serve(db: Arc<dyn DatabaseAdapter>, confirm_threshold: usize) -> anyhow::Result<()> {
+    serve_client(db, confirm_threshold, true, io::stdin(), io::stdout()).await
+}
+
+/// Serve one client speaking newline-delimited JSON-RPC until it closes
+/// `reader`. Each connection gets its own dispatcher and subscriptions.
+pub async fn serve_connection<R, W>(
+    db: Arc<dyn DatabaseAdapter>,
+    confirm_threshold: usize,
+    reader: R,
+    writer: W,
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    serve_client(db, confirm_threshold, false, reader, writer).await
+}
+
+async fn serve_client<R, W>(
+    db: Arc<dyn DatabaseAdapter>,
+    confirm_threshold: usize,
+    owner: bool,
+    reader: R,
+    mut writer: W,
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    // Responses and resource notifications share the writer through one task
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+    let subscriptions = resources::Subscriptions::default();
+    let server = Server::new(db.clone(), subscriptions.clone(), outgoing.clone())
+        .with_transport_owner(owner)
+        .with_confirm_threshold(confirm_threshold);
+    let write_messages = async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let message_json = serde_json::to_string(&message)?;
+            // Logging a log notification would send it back to the client forever
+            let is_log = message.get("method").and_then(|m| m.as_str())
+                == Some("notifications/message");
+            if !is_log {
+                tracing::debug!("Sending: {}", message_json);
+            }
+            writer.write_all(message_json.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+        }
+        anyhow::Ok(())
+    };
+    let writer_task = tokio::spawn(write_messages.instrument(server.span()));
+
+    let forwarder = tokio::spawn(
+        resources::forward_changes(db, subscriptions, outgoing.clone()).instrument(server.span()),
+    );
+
+    // One message per line; the reader keeps reading while requests run
+    let (incoming, mut incoming_rx) = mpsc::channel::<String>(MAX_CONCURRENT_REQUESTS);
+    let control = server.clone();
+    let read_messages = async move {
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            let bytes_read = reader.read_line(&mut line).await?;
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            let message = line.trim();
+            if message.is_empty() {
+                continue;
+            }
+
+            tracing::debug!("Received: {}", message);
+            // Running handlers may be waiting for these, so they skip the queue
+            if let Ok(value) = serde_json::from_str(message) {
+                if server::is_control_message(&value) {
+                    control.handle_json(value).await;
+                    continue;
+                }
+            }
+            if incoming.send(message.to_string()).await.is_err() {
+                break;
+            }
+        }
+        anyhow::Ok(())
+    };
+    let reader_task = tokio::spawn(read_messages.instrument(server.span()));
+
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+    let mut handlers = JoinSet::new();
+
+    // Responses are sent as each handler finishes, so they may arrive out of order
+    while let Some(message) = incoming_rx.recv().await {
+        let permit = permits.clone().acquire_owned().await?;
+        let server = server.clone();
+        let outgoing = outgoing.clone();
+        handlers.spawn(async move {
+            if let Some(response) = server.handle_message(&message).await {
+                let _ = outgoing.send(response);
+            }
+            drop(permit);
+        });
+        while handlers.try_join_next().is_some() {}
+    }
+
+    // Finish in-flight requests before closing the writer
+    while handlers.join_next().await.is_some() {}
+    reader_task.await??;
+    forwarder.abort();
+    // The writer stops once every sender, including the server's, is gone
+    drop(server);
+    drop(outgoing);
+    writer_task.await??;
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use crate::db::adapter::{AlterTableOperation, AlterTableResult};
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AlterTableInput {
+    /// Table name
+    pub table: String,
+    pub operation: AlterTableOperation,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: AlterTableInput = parse_arguments(args)?;
+    let result: AlterTableResult = db.alter_table(&input.table, input.operation).await?;
+    ToolOutput::json(&result)
+}
@@ -1,11 +1,17 @@
+use crate::db::error::DbError;
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct BatchInsertInput {
+    /// Table name
     pub table: String,
+    /// Array of records to insert
+    #[schemars(length(max = 100))]
     pub items: Vec<serde_json::Value>,
+    /// Number of records per transaction
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
 }
@@ -26,11 +32,14 @@ pub struct BatchInsertOutput {
 pub async fn insert_execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: BatchInsertInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: BatchInsertInput = parse_arguments(args)?;
 
     if input.items.len() > 100 {
-        return Err(anyhow::anyhow!("Maximum of 100 items allowed per batch"));
+        return Err(DbError::LimitExceeded(
+            "Maximum of 100 items allowed per batch".to_string(),
+        )
+        .into());
     }
 
     let result = db.batch_insert(&input.table, input.items, input.batch_size).await?;
@@ -41,20 +50,26 @@ pub async fn insert_execute(
         errors: result.errors,
         inserted_ids: result.inserted_ids,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct BatchUpdateInput {
+    /// Table name
     pub table: String,
+    /// Array of {id, data} objects
+    #[schemars(length(max = 100))]
     pub updates: Vec<BatchUpdateItem>,
+    /// Number of records per transaction
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct BatchUpdateItem {
+    /// Record ID
     pub id: i64,
+    /// Updated data as JSON object
     pub data: serde_json::Value,
 }
 
@@ -69,11 +84,14 @@ pub struct BatchUpdateOutput {
 pub async fn update_execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: BatchUpdateInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: BatchUpdateInput = parse_arguments(args)?;
 
     if input.updates.len() > 100 {
-        return Err(anyhow::anyhow!("Maximum of 100 updates allowed per batch"));
+        return Err(DbError::LimitExceeded(
+            "Maximum of 100 updates allowed per batch".to_string(),
+        )
+        .into());
     }
 
     let updates: Vec<(i64, serde_json::Value)> = input
@@ -89,12 +107,14 @@ pub async fn update_execute(
         failed: result.failed,
         errors: result.errors,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct BatchDeleteInput {
+    /// Table name
     pub table: String,
+    /// Array of record IDs to delete
     pub ids: Vec<i64>,
 }
 
@@ -106,12 +126,12 @@ pub struct BatchDeleteOutput {
 pub async fn delete_execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: BatchDeleteInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: BatchDeleteInput = parse_arguments(args)?;
 
     let affected = db.batch_delete(&input.table, input.ids).await?;
     let output = BatchDeleteOutput {
         affected_rows: affected as u64,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -0,0 +1,70 @@
+use crate::db::adapter::{ColumnDefinition, IndexDefinition, TableDefinition, TableSchema};
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub async fn create_table_execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: TableDefinition = parse_arguments(args)?;
+    let schema: TableSchema = db.create_table(input).await?;
+    ToolOutput::json(&schema)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AddColumnInput {
+    /// Table name
+    pub table: String,
+    pub column: ColumnDefinition,
+}
+
+pub async fn add_column_execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: AddColumnInput = parse_arguments(args)?;
+    let schema: TableSchema = db.add_column(&input.table, input.column).await?;
+    ToolOutput::json(&schema)
+}
+
+pub async fn create_index_execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: IndexDefinition = parse_arguments(args)?;
+    let schema: TableSchema = db.create_index(input).await?;
+    ToolOutput::json(&schema)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DropIndexInput {
+    /// Index name
+    pub name: String,
+    /// Do not fail if the index does not exist
+    #[serde(default)]
+    pub if_exists: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DropIndexOutput {
+    pub name: String,
+    pub dropped: bool,
+    /// Schema of the table the index belonged to; absent if nothing was dropped
+    pub schema: Option<TableSchema>,
+}
+
+pub async fn drop_index_execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: DropIndexInput = parse_arguments(args)?;
+    let schema = db.drop_index(&input.name, input.if_exists).await?;
+    let output = DropIndexOutput {
+        name: input.name,
+        dropped: schema.is_some(),
+        schema,
+    };
+    ToolOutput::json(&output)
+}
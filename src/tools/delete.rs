@@ -1,10 +1,13 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DeleteRecordInput {
+    /// Table name
     pub table: String,
+    /// Record ID
     pub id: i64,
 }
 
@@ -16,11 +19,11 @@ pub struct DeleteRecordOutput {
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: DeleteRecordInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: DeleteRecordInput = parse_arguments(args)?;
     let affected = db.delete(&input.table, input.id).await?;
     let output = DeleteRecordOutput {
         affected_rows: affected as u64,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -0,0 +1,161 @@
+use crate::db::adapter::TableSchema;
+use crate::db::progress;
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DictionaryFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExportDataDictionaryInput {
+    /// Output format
+    #[serde(default)]
+    pub format: DictionaryFormat,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DataDictionary {
+    pub tables: Vec<TableEntry>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TableEntry {
+    #[serde(flatten)]
+    pub schema: TableSchema,
+    pub row_count: usize,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: ExportDataDictionaryInput = parse_arguments(args)?;
+    match input.format {
+        DictionaryFormat::Markdown => Ok(ToolOutput::text(export(db, input.format).await?)),
+        DictionaryFormat::Json => ToolOutput::json(&collect(db).await?),
+    }
+}
+
+async fn collect(db: &std::sync::Arc<dyn DatabaseAdapter>) -> anyhow::Result<DataDictionary> {
+    let schemas = db.list_schemas().await?;
+    let total = schemas.len();
+    let mut tables = Vec::new();
+    for schema in schemas {
+        let row_count = db.count(&schema.name, None).await?;
+        tables.push(TableEntry { schema, row_count });
+        progress::report(tables.len(), total, || {
+            format!("Counted rows in {} of {} tables", tables.len(), total)
+        });
+    }
+    Ok(DataDictionary { tables })
+}
+
+/// Render the whole database as a data dictionary. Shared by the tool and
+/// the `data-dictionary` subcommand.
+pub async fn export(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    format: DictionaryFormat,
+) -> anyhow::Result<String> {
+    let dictionary = collect(db).await?;
+
+    match format {
+        DictionaryFormat::Markdown => Ok(render_markdown(&dictionary)),
+        DictionaryFormat::Json => {
+            serde_json::to_string_pretty(&dictionary).map_err(anyhow::Error::from)
+        }
+    }
+}
+
+/// Keep cell text from breaking the table layout.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn render_markdown(dictionary: &DataDictionary) -> String {
+    let mut out = String::from("# 数据字典\n");
+
+    for TableEntry { schema, row_count } in &dictionary.tables {
+        out.push_str(&format!("\n## {}\n\n", schema.name));
+        if let Some(desc) = schema.desc.as_deref().filter(|d| *d != schema.name) {
+            out.push_str(&format!("{}\n\n", desc));
+        }
+        out.push_str(&format!("行数：{}\n\n", row_count));
+
+        out.push_str("| 列 | 类型 | 约束 | 默认值 | 说明 |\n");
+        out.push_str("|----|------|------|--------|------|\n");
+        for column in &schema.columns {
+            let mut constraints = Vec::new();
+            if column.is_primary_key {
+                constraints.push("PK".to_string());
+            }
+            if column.not_null {
+                constraints.push("NOT NULL".to_string());
+            }
+            if schema
+                .indexes
+                .iter()
+                .any(|i| i.unique && i.origin != "pk" && i.columns == [column.name.clone()])
+            {
+                constraints.push("UNIQUE".to_string());
+            }
+            for fk in &schema.foreign_keys {
+                if let Some(i) = fk.columns.iter().position(|c| c == &column.name) {
+                    constraints.push(match fk.ref_columns.get(i) {
+                        Some(ref_column) => format!("FK → {}.{}", fk.ref_table, ref_column),
+                        None => format!("FK → {}", fk.ref_table),
+                    });
+                }
+            }
+
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                cell(&column.name),
+                cell(&column.data_type),
+                constraints.join(", "),
+                column.default_value.as_deref().map(cell).unwrap_or_default(),
+                column
+                    .desc
+                    .as_deref()
+                    .filter(|d| *d != column.name)
+                    .map(cell)
+                    .unwrap_or_default()
+            ));
+        }
+
+        let table_constraints: Vec<String> = schema
+            .indexes
+            .iter()
+            .filter(|i| i.unique && i.origin == "u" && i.columns.len() > 1)
+            .map(|i| format!("UNIQUE ({})", i.columns.join(", ")))
+            .chain(schema.checks.iter().map(|c| format!("CHECK ({})", c)))
+            .collect();
+        if !table_constraints.is_empty() {
+            out.push_str("\n约束：\n\n");
+            for constraint in table_constraints {
+                out.push_str(&format!("- `{}`\n", constraint));
+            }
+        }
+
+        let indexes: Vec<_> = schema.indexes.iter().filter(|i| i.origin == "c").collect();
+        if !indexes.is_empty() {
+            out.push_str("\n索引：\n\n");
+            for index in indexes {
+                out.push_str(&format!(
+                    "- `{}`{}: {}\n",
+                    index.name,
+                    if index.unique { " (UNIQUE)" } else { "" },
+                    index.columns.join(", ")
+                ));
+            }
+        }
+    }
+
+    out
+}
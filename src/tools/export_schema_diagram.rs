@@ -0,0 +1,237 @@
+use crate::db::adapter::{ColumnInfo, TableSchema};
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    /// Mermaid `erDiagram`
+    #[default]
+    Mermaid,
+    /// Graphviz DOT
+    Dot,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExportSchemaDiagramInput {
+    /// Diagram format
+    #[serde(default)]
+    pub format: DiagramFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExportSchemaDiagramOutput {
+    pub format: DiagramFormat,
+    pub diagram: String,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: ExportSchemaDiagramInput = parse_arguments(args)?;
+    let schemas = db.list_schemas().await?;
+    let diagram = match input.format {
+        DiagramFormat::Mermaid => render_mermaid(&schemas),
+        DiagramFormat::Dot => render_dot(&schemas),
+    };
+    let output = ExportSchemaDiagramOutput {
+        format: input.format,
+        diagram,
+    };
+    ToolOutput::json(&output)
+}
+
+/// The stored comment, unless it only repeats the name.
+fn label<'a>(desc: &'a Option<String>, name: &str) -> Option<&'a str> {
+    desc.as_deref().filter(|d| !d.is_empty() && *d != name)
+}
+
+fn is_foreign_key(schema: &TableSchema, column: &ColumnInfo) -> bool {
+    schema
+        .foreign_keys
+        .iter()
+        .any(|fk| fk.columns.contains(&column.name))
+}
+
+fn mermaid_name(name: &str) -> String {
+    let plain = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain && !name.is_empty() {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "'"))
+    }
+}
+
+/// Mermaid attribute names are single words.
+fn mermaid_attribute(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Mermaid attribute types are single words.
+fn mermaid_type(data_type: &str) -> String {
+    let data_type: String = data_type
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '(' || c == ')' { c } else { '_' })
+        .collect();
+    if data_type.is_empty() {
+        "ANY".to_string()
+    } else {
+        data_type
+    }
+}
+
+fn render_mermaid(schemas: &[TableSchema]) -> String {
+    let mut lines = vec!["erDiagram".to_string()];
+
+    for schema in schemas {
+        // The alias replaces the displayed name, so it carries both
+        let alias = label(&schema.desc, &schema.name)
+            .map(|desc| {
+                let text = format!("{} ({})", schema.name, desc);
+                format!("[\"{}\"]", text.replace('"', "'").replace('\n', " "))
+            })
+            .unwrap_or_default();
+        lines.push(format!("    {}{} {{", mermaid_name(&schema.name), alias));
+        for column in &schema.columns {
+            let mut keys = Vec::new();
+            if column.is_primary_key {
+                keys.push("PK");
+            }
+            if is_foreign_key(schema, column) {
+                keys.push("FK");
+            }
+
+            let mut line = format!(
+                "        {} {}",
+                mermaid_type(&column.data_type),
+                mermaid_attribute(&column.name)
+            );
+            if !keys.is_empty() {
+                line.push_str(&format!(" {}", keys.join(",")));
+            }
+            if let Some(desc) = label(&column.desc, &column.name) {
+                line.push_str(&format!(" \"{}\"", desc.replace('"', "'").replace('\n', " ")));
+            }
+            lines.push(line);
+        }
+        lines.push("    }".to_string());
+    }
+
+    for schema in schemas {
+        for fk in &schema.foreign_keys {
+            // A nullable foreign key means the parent is optional
+            let required = fk.columns.iter().all(|name| {
+                schema
+                    .columns
+                    .iter()
+                    .any(|c| &c.name == name && (c.not_null || c.is_primary_key))
+            });
+            lines.push(format!(
+                "    {} {}--o{{ {} : \"{}\"",
+                mermaid_name(&fk.ref_table),
+                if required { "||" } else { "|o" },
+                mermaid_name(&schema.name),
+                fk.columns.join(", ")
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Port of a column in its table's record, by position, so any column name
+/// can be an edge endpoint.
+fn dot_port(schemas: &[TableSchema], table: &str, column: Option<&String>) -> String {
+    let position = schemas
+        .iter()
+        .find(|s| s.name == table)
+        .and_then(|s| match column {
+            Some(column) => s.columns.iter().position(|c| &c.name == column),
+            None => s.columns.iter().position(|c| c.is_primary_key),
+        });
+    match position {
+        Some(i) => format!(":c{}", i),
+        None => String::new(),
+    }
+}
+
+fn render_dot(schemas: &[TableSchema]) -> String {
+    let mut lines = vec![
+        "digraph schema {".to_string(),
+        "    rankdir=LR;".to_string(),
+        "    node [shape=plaintext];".to_string(),
+    ];
+
+    for schema in schemas {
+        let mut rows = vec![format!(
+            "<tr><td bgcolor=\"lightgrey\" colspan=\"3\"><b>{}</b>{}</td></tr>",
+            html_escape(&schema.name),
+            label(&schema.desc, &schema.name)
+                .map(|d| format!("<br/><i>{}</i>", html_escape(d)))
+                .unwrap_or_default()
+        )];
+        for (i, column) in schema.columns.iter().enumerate() {
+            let mut keys = Vec::new();
+            if column.is_primary_key {
+                keys.push("PK");
+            }
+            if is_foreign_key(schema, column) {
+                keys.push("FK");
+            }
+            rows.push(format!(
+                "<tr><td port=\"c{}\" align=\"left\">{}{}</td><td align=\"left\">{}</td><td align=\"left\">{}</td></tr>",
+                i,
+                html_escape(&column.name),
+                if keys.is_empty() {
+                    String::new()
+                } else {
+                    format!(" <b>{}</b>", keys.join(","))
+                },
+                html_escape(&column.data_type),
+                label(&column.desc, &column.name)
+                    .map(html_escape)
+                    .unwrap_or_default()
+            ));
+        }
+        lines.push(format!(
+            "    {} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\">{}</table>>];",
+            dot_id(&schema.name),
+            rows.join("")
+        ));
+    }
+
+    for schema in schemas {
+        for fk in &schema.foreign_keys {
+            lines.push(format!(
+                "    {}{} -> {}{} [label={}];",
+                dot_id(&schema.name),
+                dot_port(schemas, &schema.name, fk.columns.first()),
+                dot_id(&fk.ref_table),
+                dot_port(schemas, &fk.ref_table, fk.ref_columns.first()),
+                dot_id(&fk.columns.join(", "))
+            ));
+        }
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
@@ -1,17 +1,19 @@
 use crate::db::{adapter::TableSchema, DatabaseAdapter};
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct GetTableSchemaInput {
+    /// Table name
     pub table: String,
 }
 
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: GetTableSchemaInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: GetTableSchemaInput = parse_arguments(args)?;
     let schema: TableSchema = db.get_schema(&input.table).await?;
-    serde_json::to_string_pretty(&schema).map_err(anyhow::Error::from)
+    ToolOutput::json(&schema)
 }
@@ -0,0 +1,49 @@
+use crate::db::adapter::{CommentDocument, ImportCommentsResult};
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, InvalidArguments, ToolOutput};
+use anyhow::anyhow;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ImportCommentsInput {
+    /// Inline document: `{table: {desc, columns: {column: desc}}}`
+    pub comments: Option<CommentDocument>,
+    /// JSON or YAML file holding the document, instead of `comments`
+    pub path: Option<PathBuf>,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: ImportCommentsInput = parse_arguments(args)?;
+    let document = match (input.comments, input.path) {
+        (Some(comments), None) => comments,
+        (None, Some(path)) => load_document(&path)?,
+        _ => {
+            return Err(InvalidArguments(
+                "Exactly one of 'comments' or 'path' must be given".to_string(),
+            )
+            .into())
+        }
+    };
+
+    let result: ImportCommentsResult = db.import_comments(document).await?;
+    ToolOutput::json(&result)
+}
+
+/// Read a comment document from a file; `.yaml`/`.yml` files are parsed as
+/// YAML, anything else as JSON.
+pub fn load_document(path: &Path) -> anyhow::Result<CommentDocument> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+            .map_err(|e| anyhow!("Invalid YAML comment document {:?}: {}", path, e)),
+        _ => serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid JSON comment document {:?}: {}", path, e)),
+    }
+}
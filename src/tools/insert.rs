@@ -1,15 +1,19 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct InsertRecordInput {
+    /// Table name
     pub table: String,
+    /// Record data as JSON object
     pub data: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct InsertRecordOutput {
+    /// Row ID of the inserted record
     pub id: i64,
     pub affected_rows: u64,
 }
@@ -17,12 +21,12 @@ pub struct InsertRecordOutput {
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: InsertRecordInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: InsertRecordInput = parse_arguments(args)?;
     let id = db.insert(&input.table, input.data).await?;
     let output = InsertRecordOutput {
         id,
         affected_rows: 1,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -1,7 +1,15 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ListTablesInput {
+    /// Also list the server's internal tables (comments, migrations)
+    #[serde(default)]
+    pub include_internal: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ListTablesOutput {
     pub tables: Vec<String>,
@@ -9,9 +17,10 @@ pub struct ListTablesOutput {
 
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
-    _args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let tables = db.list_tables().await?;
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: ListTablesInput = parse_arguments(args)?;
+    let tables = db.list_tables(input.include_internal).await?;
     let output = ListTablesOutput { tables };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
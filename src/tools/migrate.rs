@@ -0,0 +1,29 @@
+use crate::db::migrations::{MigrationCommand, MigrationReport};
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MigrateInput {
+    /// Directory containing migration files
+    pub directory: std::path::PathBuf,
+    pub command: MigrationCommand,
+    /// up: apply only up to this version; down: revert every migration above this version
+    #[serde(default)]
+    pub target: Option<i64>,
+    /// Report the statements that would run without executing them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: MigrateInput = parse_arguments(args)?;
+    let report: MigrationReport = db
+        .migrate(&input.directory, input.command, input.target, input.dry_run)
+        .await?;
+    ToolOutput::json(&report)
+}
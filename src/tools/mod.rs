@@ -1,9 +1,376 @@
+pub mod alter_table;
 pub mod batch;
+pub mod ddl;
 pub mod delete;
+pub mod export_data_dictionary;
+pub mod export_schema_diagram;
 pub mod get_schema;
+pub mod import_comments;
 pub mod insert;
 pub mod list_tables;
+pub mod migrate;
 pub mod query;
+pub mod schema_diff;
 pub mod set_table_comment;
 pub mod set_column_comment;
+pub mod set_column_metadata;
 pub mod update;
+
+use crate::auth::Scope;
+use crate::db::adapter::{
+    AlterTableResult, ImportCommentsResult, IndexDefinition, TableDefinition, TableSchema,
+};
+use crate::db::migrations::MigrationReport;
+use crate::db::schema_diff::SchemaDiff;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Tool arguments that do not match the tool's input schema. Reported as a
+/// JSON-RPC invalid params error rather than a tool result.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid arguments: {0}")]
+pub struct InvalidArguments(pub String);
+
+pub fn parse_arguments<T: DeserializeOwned>(
+    args: &serde_json::Value,
+) -> Result<T, InvalidArguments> {
+    serde_json::from_value(args.clone()).map_err(|e| InvalidArguments(e.to_string()))
+}
+
+/// Result of a tool call: pretty-printed text for the `content` block and,
+/// for JSON results, the same value as `structuredContent`.
+#[derive(Debug)]
+pub struct ToolOutput {
+    pub text: String,
+    pub structured: Option<serde_json::Value>,
+}
+
+impl ToolOutput {
+    pub fn json<T: Serialize>(value: &T) -> anyhow::Result<Self> {
+        Ok(Self {
+            text: serde_json::to_string_pretty(value)?,
+            structured: Some(serde_json::to_value(value)?),
+        })
+    }
+
+    pub fn text(text: String) -> Self {
+        Self {
+            text,
+            structured: None,
+        }
+    }
+}
+
+/// Behaviour hints for hosts deciding which calls need the user's approval.
+#[derive(Debug, Clone, Copy)]
+pub struct Annotations {
+    /// Never modifies the database
+    pub read_only: bool,
+    /// May overwrite or remove existing data
+    pub destructive: bool,
+    /// Repeating the call with the same arguments has no further effect
+    pub idempotent: bool,
+}
+
+impl Annotations {
+    const READ_ONLY: Self = Self {
+        read_only: true,
+        destructive: false,
+        idempotent: true,
+    };
+    /// Only adds rows or schema objects
+    const ADDITIVE: Self = Self {
+        read_only: false,
+        destructive: false,
+        idempotent: false,
+    };
+    /// Overwrites or deletes by key; a repeat finds nothing left to change
+    const DESTRUCTIVE: Self = Self {
+        read_only: false,
+        destructive: true,
+        idempotent: true,
+    };
+    /// Rewrites the schema; a repeat may change it again
+    const SCHEMA_CHANGE: Self = Self {
+        read_only: false,
+        destructive: true,
+        idempotent: false,
+    };
+}
+
+/// A tool advertised by `tools/list`. Schemas are generated from the
+/// `JsonSchema` derives on the tool's input and output types.
+pub struct Tool {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Scope a client needs to call the tool
+    pub scope: Scope,
+    pub annotations: Annotations,
+    input_schema: fn() -> serde_json::Value,
+    output_schema: Option<fn() -> serde_json::Value>,
+}
+
+impl Tool {
+    pub fn definition(&self) -> serde_json::Value {
+        let mut definition = serde_json::json!({
+            "name": self.name,
+            "title": self.title,
+            "description": self.description,
+            "inputSchema": (self.input_schema)(),
+            "annotations": {
+                "title": self.title,
+                "readOnlyHint": self.annotations.read_only,
+                "destructiveHint": self.annotations.destructive,
+                "idempotentHint": self.annotations.idempotent,
+                // Tools only touch the local database
+                "openWorldHint": false
+            },
+        });
+        if let Some(output_schema) = self.output_schema {
+            definition["outputSchema"] = output_schema();
+        }
+        definition
+    }
+
+    /// Scope needed for this particular call. Arguments naming files on the
+    /// server need the admin scope even where the tool itself does not.
+    pub fn required_scope(&self, arguments: &serde_json::Value) -> Scope {
+        let reads_files = PATH_ARGUMENTS
+            .iter()
+            .any(|(tool, argument)| *tool == self.name && !arguments[*argument].is_null());
+        if reads_files {
+            self.scope.max(Scope::Admin)
+        } else {
+            self.scope
+        }
+    }
+}
+
+/// Optional tool arguments that take a server filesystem path. Tools whose
+/// every call reads files, like `migrate` and `schema_diff`, need the admin
+/// scope outright.
+const PATH_ARGUMENTS: &[(&str, &str)] = &[("import_comments", "path")];
+
+/// JSON schema of `T` with all subschemas inlined, as MCP clients expect a
+/// self-contained object schema. The type's own title and description are
+/// dropped in favour of the tool's.
+fn schema<T: JsonSchema>() -> serde_json::Value {
+    let generator = SchemaSettings::draft07()
+        .with(|s| {
+            s.inline_subschemas = true;
+            s.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<T>())
+        .unwrap_or_else(|_| serde_json::json!({"type": "object"}));
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("title");
+        object.remove("description");
+    }
+    schema
+}
+
+pub const TOOLS: &[Tool] = &[
+    Tool {
+        name: "list_tables",
+        title: "List Tables",
+        scope: Scope::Read,
+        annotations: Annotations::READ_ONLY,
+        description: "List all tables in the database. Internal tables (_table_comment, _table_column_comment, _migrations) are hidden unless include_internal is set",
+        input_schema: schema::<list_tables::ListTablesInput>,
+        output_schema: Some(schema::<list_tables::ListTablesOutput>),
+    },
+    Tool {
+        name: "get_table_schema",
+        title: "Get Table Schema",
+        scope: Scope::Read,
+        annotations: Annotations::READ_ONLY,
+        description: "Get the schema of a specific table",
+        input_schema: schema::<get_schema::GetTableSchemaInput>,
+        output_schema: Some(schema::<TableSchema>),
+    },
+    Tool {
+        name: "query_records",
+        title: "Query Records",
+        scope: Scope::Read,
+        annotations: Annotations::READ_ONLY,
+        description: "Query records from a table with optional filters and ordering. Returns: records (array), total (count of all matching records before limit/offset), returned (count of records in this response)",
+        input_schema: schema::<query::QueryRecordsInput>,
+        output_schema: Some(schema::<query::QueryRecordsOutput>),
+    },
+    Tool {
+        name: "insert_record",
+        title: "Insert Record",
+        scope: Scope::Write,
+        annotations: Annotations::ADDITIVE,
+        description: "Insert a new record into a table",
+        input_schema: schema::<insert::InsertRecordInput>,
+        output_schema: Some(schema::<insert::InsertRecordOutput>),
+    },
+    Tool {
+        name: "update_record",
+        title: "Update Record",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Update an existing record by ID",
+        input_schema: schema::<update::UpdateRecordInput>,
+        output_schema: Some(schema::<update::UpdateRecordOutput>),
+    },
+    Tool {
+        name: "delete_record",
+        title: "Delete Record",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Delete a record by ID",
+        input_schema: schema::<delete::DeleteRecordInput>,
+        output_schema: Some(schema::<delete::DeleteRecordOutput>),
+    },
+    Tool {
+        name: "batch_insert",
+        title: "Batch Insert",
+        scope: Scope::Write,
+        annotations: Annotations::ADDITIVE,
+        description: "Insert multiple records (max 100 items)",
+        input_schema: schema::<batch::BatchInsertInput>,
+        output_schema: Some(schema::<batch::BatchInsertOutput>),
+    },
+    Tool {
+        name: "batch_update",
+        title: "Batch Update",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Update multiple records (max 100 items)",
+        input_schema: schema::<batch::BatchUpdateInput>,
+        output_schema: Some(schema::<batch::BatchUpdateOutput>),
+    },
+    Tool {
+        name: "batch_delete",
+        title: "Batch Delete",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Delete multiple records by IDs",
+        input_schema: schema::<batch::BatchDeleteInput>,
+        output_schema: Some(schema::<batch::BatchDeleteOutput>),
+    },
+    Tool {
+        name: "set_table_comment",
+        title: "Set Table Comment",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Set or update the description of a table",
+        input_schema: schema::<set_table_comment::SetTableCommentInput>,
+        output_schema: Some(schema::<set_table_comment::SetTableCommentOutput>),
+    },
+    Tool {
+        name: "set_column_comment",
+        title: "Set Column Comment",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Set or update the description of a column",
+        input_schema: schema::<set_column_comment::SetColumnCommentInput>,
+        output_schema: Some(schema::<set_column_comment::SetColumnCommentOutput>),
+    },
+    Tool {
+        name: "set_column_metadata",
+        title: "Set Column Metadata",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Set the structured metadata of a column: unit, allowed values, examples, format, sensitivity and deprecation. Replaces any previous metadata of the column. Writes are validated against allowed_values, and values of pii/secret columns are masked in query results",
+        input_schema: schema::<set_column_metadata::SetColumnMetadataInput>,
+        output_schema: Some(schema::<set_column_metadata::SetColumnMetadataOutput>),
+    },
+    Tool {
+        name: "create_table",
+        title: "Create Table",
+        scope: Scope::Admin,
+        annotations: Annotations::ADDITIVE,
+        description: "Create a table from a structured definition. Descriptions are stored as table/column comments. Returns the resulting table schema",
+        input_schema: schema::<TableDefinition>,
+        output_schema: Some(schema::<TableSchema>),
+    },
+    Tool {
+        name: "add_column",
+        title: "Add Column",
+        scope: Scope::Admin,
+        annotations: Annotations::ADDITIVE,
+        description: "Add a column to an existing table. Returns the resulting table schema",
+        input_schema: schema::<ddl::AddColumnInput>,
+        output_schema: Some(schema::<TableSchema>),
+    },
+    Tool {
+        name: "create_index",
+        title: "Create Index",
+        scope: Scope::Admin,
+        annotations: Annotations::ADDITIVE,
+        description: "Create an index on a table. Returns the resulting table schema",
+        input_schema: schema::<IndexDefinition>,
+        output_schema: Some(schema::<TableSchema>),
+    },
+    Tool {
+        name: "drop_index",
+        title: "Drop Index",
+        scope: Scope::Admin,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Drop an index. Returns the schema of the table it belonged to",
+        input_schema: schema::<ddl::DropIndexInput>,
+        output_schema: Some(schema::<ddl::DropIndexOutput>),
+    },
+    Tool {
+        name: "alter_table",
+        title: "Alter Table",
+        scope: Scope::Admin,
+        annotations: Annotations::SCHEMA_CHANGE,
+        description: "Alter a table: rename_table, rename_column, drop_column, change_type or add_constraint. Uses native ALTER TABLE where SQLite supports it and otherwise rebuilds the table (copy, drop, rename) with foreign keys checked afterwards. Column comments follow renames. Returns the strategy used and the resulting table schema",
+        input_schema: schema::<alter_table::AlterTableInput>,
+        output_schema: Some(schema::<AlterTableResult>),
+    },
+    Tool {
+        name: "migrate",
+        title: "Migrate",
+        scope: Scope::Admin,
+        annotations: Annotations::SCHEMA_CHANGE,
+        description: "Run versioned schema migrations from a directory of <version>_<name>.<up|down>.<sql|json> files. Applied versions and checksums are tracked in _migrations; each migration runs in its own transaction",
+        input_schema: schema::<migrate::MigrateInput>,
+        output_schema: Some(schema::<MigrationReport>),
+    },
+    Tool {
+        name: "schema_diff",
+        title: "Schema Diff",
+        scope: Scope::Admin,
+        annotations: Annotations::READ_ONLY,
+        description: "Compare the schema of two SQLite database files table by table: added, removed and changed tables, columns, foreign keys, indexes, triggers and comments. Optionally generate the SQL that migrates source to target",
+        input_schema: schema::<schema_diff::SchemaDiffInput>,
+        output_schema: Some(schema::<SchemaDiff>),
+    },
+    Tool {
+        name: "export_schema_diagram",
+        title: "Export Schema Diagram",
+        scope: Scope::Read,
+        annotations: Annotations::READ_ONLY,
+        description: "Render all tables and their foreign key relationships as a Mermaid erDiagram or Graphviz DOT diagram, with column types, PK/FK markers and comments as labels",
+        input_schema: schema::<export_schema_diagram::ExportSchemaDiagramInput>,
+        output_schema: Some(schema::<export_schema_diagram::ExportSchemaDiagramOutput>),
+    },
+    Tool {
+        name: "export_data_dictionary",
+        title: "Export Data Dictionary",
+        scope: Scope::Read,
+        annotations: Annotations::READ_ONLY,
+        description: "Export the whole database as a data dictionary: tables, columns, types, constraints, comments and row counts, as Markdown or a single JSON document",
+        input_schema: schema::<export_data_dictionary::ExportDataDictionaryInput>,
+        // Markdown output is plain text; only the JSON format is structured
+        output_schema: None,
+    },
+    Tool {
+        name: "import_comments",
+        title: "Import Comments",
+        scope: Scope::Write,
+        annotations: Annotations::DESTRUCTIVE,
+        description: "Set table and column comments in bulk from a nested document {table: {desc, columns: {column: desc}}}, inline or from a JSON/YAML file. Everything is applied in one transaction; unknown tables and columns are skipped and reported",
+        input_schema: schema::<import_comments::ImportCommentsInput>,
+        output_schema: Some(schema::<ImportCommentsResult>),
+    },
+];
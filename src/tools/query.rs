@@ -1,16 +1,22 @@
 use crate::db::{adapter::QueryFilter, DatabaseAdapter, OrderClause};
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct QueryRecordsInput {
+    /// Table name
     pub table: String,
+    /// Filter conditions. Multiple columns are combined with AND. Supported operators: $eq, $ne, $gt, $gte, $lt, $lte, $in, $like
     #[serde(default)]
     pub filters: Option<QueryFilter>,
+    /// Ordering rules, either {"column", "direction"} or {"random": true}
     #[serde(default)]
     pub orders: Option<Vec<OrderClause>>,
+    /// Maximum number of results
     #[serde(default)]
     pub limit: Option<usize>,
+    /// Offset for pagination
     #[serde(default)]
     pub offset: Option<usize>,
 }
@@ -18,15 +24,17 @@ pub struct QueryRecordsInput {
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct QueryRecordsOutput {
     pub records: Vec<serde_json::Value>,
+    /// Count of all matching records before limit/offset
     pub total: usize,
+    /// Count of records in this response
     pub returned: usize,
 }
 
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: QueryRecordsInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: QueryRecordsInput = parse_arguments(args)?;
     let total = db.count(&input.table, input.filters.clone()).await?;
     let records = db.select(&input.table, input.filters, input.orders, input.limit, input.offset).await?;
     let returned = records.len();
@@ -35,5 +43,5 @@ pub async fn execute(
         total,
         returned,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -0,0 +1,27 @@
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SchemaDiffInput {
+    /// Database file with the desired schema
+    pub target: PathBuf,
+    /// Database file to compare from; defaults to the server's database
+    pub source: Option<PathBuf>,
+    /// Also generate the SQL that migrates source to target
+    #[serde(default)]
+    pub include_sql: bool,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: SchemaDiffInput = parse_arguments(args)?;
+    let diff = db
+        .schema_diff(input.source.as_deref(), &input.target, input.include_sql)
+        .await?;
+    ToolOutput::json(&diff)
+}
@@ -1,11 +1,15 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SetColumnCommentInput {
+    /// Table name
     pub table: String,
+    /// Column name
     pub column: String,
+    /// Column description
     pub desc: String,
 }
 
@@ -19,13 +23,13 @@ pub struct SetColumnCommentOutput {
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: SetColumnCommentInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: SetColumnCommentInput = parse_arguments(args)?;
     db.set_column_comment(&input.table, &input.column, &input.desc).await?;
     let output = SetColumnCommentOutput {
         table: input.table,
         column: input.column,
         desc: input.desc,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -0,0 +1,38 @@
+use crate::db::adapter::ColumnMetadata;
+use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SetColumnMetadataInput {
+    /// Table name
+    pub table: String,
+    /// Column name
+    pub column: String,
+    #[serde(flatten)]
+    pub metadata: ColumnMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SetColumnMetadataOutput {
+    pub table: String,
+    pub column: String,
+    #[serde(flatten)]
+    pub metadata: ColumnMetadata,
+}
+
+pub async fn execute(
+    db: &std::sync::Arc<dyn DatabaseAdapter>,
+    args: &serde_json::Value,
+) -> anyhow::Result<ToolOutput> {
+    let input: SetColumnMetadataInput = parse_arguments(args)?;
+    db.set_column_metadata(&input.table, &input.column, input.metadata.clone())
+        .await?;
+    let output = SetColumnMetadataOutput {
+        table: input.table,
+        column: input.column,
+        metadata: input.metadata,
+    };
+    ToolOutput::json(&output)
+}
@@ -1,10 +1,13 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SetTableCommentInput {
+    /// Table name
     pub table: String,
+    /// Table description
     pub desc: String,
 }
 
@@ -17,12 +20,12 @@ pub struct SetTableCommentOutput {
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: SetTableCommentInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: SetTableCommentInput = parse_arguments(args)?;
     db.set_table_comment(&input.table, &input.desc).await?;
     let output = SetTableCommentOutput {
         table: input.table,
         desc: input.desc,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
\ No newline at end of file
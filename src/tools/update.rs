@@ -1,11 +1,15 @@
 use crate::db::DatabaseAdapter;
+use crate::tools::{parse_arguments, ToolOutput};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateRecordInput {
+    /// Table name
     pub table: String,
+    /// Record ID
     pub id: i64,
+    /// Updated data as JSON object
     pub data: serde_json::Value,
 }
 
@@ -17,11 +21,11 @@ pub struct UpdateRecordOutput {
 pub async fn execute(
     db: &std::sync::Arc<dyn DatabaseAdapter>,
     args: &serde_json::Value,
-) -> anyhow::Result<String> {
-    let input: UpdateRecordInput = serde_json::from_value(args.clone())?;
+) -> anyhow::Result<ToolOutput> {
+    let input: UpdateRecordInput = parse_arguments(args)?;
     let affected = db.update(&input.table, input.id, input.data).await?;
     let output = UpdateRecordOutput {
         affected_rows: affected as u64,
     };
-    serde_json::to_string_pretty(&output).map_err(anyhow::Error::from)
+    ToolOutput::json(&output)
 }
@@ -0,0 +1,79 @@
+//! Unix domain socket transport. Every connection speaks the same
+//! newline-delimited JSON-RPC as stdio and gets its own session; all of them
+//! share one database.
+
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use tokio::net::UnixListener;
+use tracing::info;
+
+use crate::db::DatabaseAdapter;
+use crate::stdio;
+
+pub async fn serve(
+    db: Arc<dyn DatabaseAdapter>,
+    path: &Path,
+    confirm_threshold: usize,
+) -> anyhow::Result<()> {
+    remove_stale_socket(path)?;
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind socket {:?}", path))?;
+    // Only the owner may connect; there is no authentication on the socket
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    info!("Listening on {:?}", path);
+
+    let result = accept_connections(db, &listener, confirm_threshold).await;
+
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+async fn accept_connections(
+    db: Arc<dyn DatabaseAdapter>,
+    listener: &UnixListener,
+    confirm_threshold: usize,
+) -> anyhow::Result<()> {
+    let mut next_id = 0u64;
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => accepted?.0,
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutting down socket server");
+                return Ok(());
+            }
+        };
+
+        next_id += 1;
+        let id = next_id;
+        info!("Client {} connected", id);
+
+        let db = db.clone();
+        tokio::spawn(async move {
+            let (reader, writer) = stream.into_split();
+            match stdio::serve_connection(db, confirm_threshold, reader, writer).await {
+                Ok(()) => info!("Client {} disconnected", id),
+                Err(e) => tracing::warn!("Client {} failed: {:#}", id, e),
+            }
+        });
+    }
+}
+
+/// A socket left behind by a previous run would make `bind` fail. Live
+/// sockets and anything that is not a socket are left alone.
+fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(anyhow!("{:?} is in use by another server", path));
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket {:?}", path))
+        }
+        Ok(_) => Err(anyhow!("{:?} exists and is not a socket", path)),
+        Err(_) => Ok(()),
+    }
+}